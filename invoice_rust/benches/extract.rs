@@ -0,0 +1,57 @@
+//! 字段识别基准测试
+//!
+//! 语料为 `benches/corpus/` 下模拟 pdf-extract 输出的发票文本，运行：
+//!
+//! ```bash
+//! cargo bench --bench extract
+//! ```
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use invoice_extractor::{parse_invoice_text, RuleSet};
+use std::fs;
+use std::path::Path;
+
+/// 读取语料目录下的所有 `.txt` 文件，按文件名排序
+fn load_corpus() -> Vec<(String, String)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("benches/corpus");
+    let mut corpus: Vec<(String, String)> = fs::read_dir(&dir)
+        .expect("read benches/corpus")
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .map(|path| {
+            let name = path.file_stem().unwrap().to_string_lossy().to_string();
+            let text = fs::read_to_string(&path).expect("read corpus file");
+            (name, text)
+        })
+        .collect();
+    corpus.sort();
+    corpus
+}
+
+fn bench_rules(c: &mut Criterion) {
+    c.bench_function("rules/compile", |b| b.iter(RuleSet::new));
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let corpus = load_corpus();
+    let rules = RuleSet::shared();
+
+    let mut group = c.benchmark_group("parse");
+    for (name, text) in &corpus {
+        group.bench_function(name.as_str(), |b| {
+            b.iter(|| parse_invoice_text(black_box(text), None, Some("示例科技"), rules))
+        });
+    }
+    group.bench_function("corpus", |b| {
+        b.iter(|| {
+            for (_, text) in &corpus {
+                black_box(parse_invoice_text(black_box(text), None, Some("示例科技"), rules));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_rules, bench_parse);
+criterion_main!(benches);
@@ -0,0 +1,183 @@
+//! 大写金额解析
+//!
+//! 发票上的价税合计同时印有大写（如"壹佰贰拾叁圆肆角伍分"）和小写金额，
+//! 大写金额不会被误识别为其他数字，用于核对和选择价税合计。
+
+/// 解析大写金额，返回以分为单位的整数
+///
+/// 支持"负"前缀（红字发票）、"圆/元"、"角"、"分"、"整/正"，以及"貳""陸""萬""億"等繁体写法。
+/// 含有其他字符、没有数字或超出 `i64` 范围时返回 `None`。例如"壹佰贰拾叁圆肆角伍分"返回 12345。
+pub fn parse_amount_in_words(words: &str) -> Option<i64> {
+    let words = words.trim();
+    let (negative, words) = match words.strip_prefix('负') {
+        Some(rest) => (true, rest),
+        None => (false, words),
+    };
+
+    // 整数部分：亿以上的总数、万以内的小节、当前数字
+    let mut total: i64 = 0;
+    let mut section: i64 = 0;
+    let mut number: i64 = 0;
+    let mut yuan: Option<i64> = None;
+    let mut cents: i64 = 0;
+    let mut has_digit = false;
+
+    for c in words.chars() {
+        if let Some(d) = digit(c) {
+            number = d;
+            has_digit = true;
+            continue;
+        }
+        match c {
+            '拾' | '佰' | '仟' => {
+                let unit = match c {
+                    '拾' => 10,
+                    '佰' => 100,
+                    _ => 1000,
+                };
+                // "拾圆" 等省略了"壹"
+                section = section.checked_add(number.max(1) * unit)?;
+                number = 0;
+                has_digit = true;
+            }
+            '万' | '萬' => {
+                total = total.checked_add(section.checked_add(number)?.checked_mul(10_000)?)?;
+                section = 0;
+                number = 0;
+            }
+            '亿' | '億' => {
+                total = total.checked_add(section)?.checked_add(number)?.checked_mul(100_000_000)?;
+                section = 0;
+                number = 0;
+            }
+            '圆' | '元' | '圓' => {
+                yuan = Some(total.checked_add(section)?.checked_add(number)?);
+                total = 0;
+                section = 0;
+                number = 0;
+            }
+            '角' => {
+                cents = cents.checked_add(number * 10)?;
+                number = 0;
+            }
+            '分' => {
+                cents = cents.checked_add(number)?;
+                number = 0;
+            }
+            '整' | '正' => break,
+            _ => return None,
+        }
+    }
+
+    if !has_digit {
+        return None;
+    }
+    // 没有"圆"时只有角分
+    let yuan = match yuan {
+        Some(yuan) => yuan,
+        None => total.checked_add(section)?,
+    };
+    let value = yuan.checked_mul(100)?.checked_add(cents)?;
+    Some(if negative { -value } else { value })
+}
+
+fn digit(c: char) -> Option<i64> {
+    let d = match c {
+        '零' | '〇' => 0,
+        '壹' => 1,
+        '贰' | '貳' => 2,
+        '叁' | '參' => 3,
+        '肆' => 4,
+        '伍' => 5,
+        '陆' | '陸' => 6,
+        '柒' => 7,
+        '捌' => 8,
+        '玖' => 9,
+        _ => return None,
+    };
+    Some(d)
+}
+
+/// 将小写金额（如 "1,234.50"）转换为分，无法解析时返回 `None`
+///
+/// 去掉千分位逗号后必须是 `-?\d+(\.\d{1,2})?` 的形式，"1e5"、"inf" 等浮点写法和超出 `i64` 的金额都返回 `None`。
+pub(crate) fn parse_cents(amount: &str) -> Option<i64> {
+    let amount = amount.replace(',', "");
+    let (negative, amount) = match amount.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, amount.as_str()),
+    };
+    let (yuan, fraction) = match amount.split_once('.') {
+        Some((yuan, fraction)) => (yuan, fraction),
+        None => (amount, ""),
+    };
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if yuan.is_empty() || amount.ends_with('.') || !all_digits(yuan) || !all_digits(fraction) || fraction.len() > 2 {
+        return None;
+    }
+    let fraction = format!("{:0<2}", fraction);
+    let value = yuan.parse::<i64>().ok()?.checked_mul(100)?.checked_add(fraction.parse::<i64>().ok()?)?;
+    Some(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_amounts_in_words() {
+        let cases = [
+            ("壹佰贰拾叁圆肆角伍分", 12345),
+            ("壹万零伍拾圆整", 1_005_000),
+            ("拾圆整", 1000),
+            ("壹拾万圆正", 10_000_000),
+            ("负壹佰圆整", -10000),
+            ("伍角", 50),
+            ("零圆零伍分", 5),
+            ("壹亿贰仟万圆整", 12_000_000_000),
+            ("貳萬陸仟元整", 2_600_000),
+            (" 叁圆 ", 300),
+            // "整"之后的文字不再解析
+            ("壹佰圆整。", 10000),
+        ];
+        for (words, cents) in cases {
+            assert_eq!(parse_amount_in_words(words), Some(cents), "{}", words);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_words() {
+        for words in ["", "整", "圆整", "负", "123.45", "壹佰贰拾叁圆A"] {
+            assert_eq!(parse_amount_in_words(words), None, "{}", words);
+        }
+    }
+
+    #[test]
+    fn rejects_overflowing_words() {
+        // 连续的"亿"每次乘以一亿，很快超出 i64
+        for words in ["壹亿亿亿圆", "玖仟亿亿圆整", "壹亿亿亿亿亿角"] {
+            assert_eq!(parse_amount_in_words(words), None, "{}", words);
+        }
+        assert_eq!(parse_amount_in_words("玖万亿圆整"), Some(9 * 10_000 * 100_000_000 * 100));
+    }
+
+    #[test]
+    fn parses_cents() {
+        assert_eq!(parse_cents("1,234.50"), Some(123_450));
+        assert_eq!(parse_cents("-0.01"), Some(-1));
+        assert_eq!(parse_cents("100"), Some(10000));
+        assert_eq!(parse_cents("0.5"), Some(50));
+        assert_eq!(parse_cents("abc"), None);
+        assert_eq!(parse_cents(""), None);
+    }
+
+    #[test]
+    fn rejects_non_decimal_amounts() {
+        for amount in [
+            "inf", "-inf", "NaN", "1e5", "1E2", "+100", "100.", ".5", "1.234", "-", "1 000",
+            "¥100", "99999999999999999999",
+        ] {
+            assert_eq!(parse_cents(amount), None, "{}", amount);
+        }
+    }
+}
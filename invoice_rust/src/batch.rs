@@ -0,0 +1,418 @@
+//! 批量处理
+//!
+//! 先遍历目录收集待处理文件，再由固定数量的工作线程并行识别，
+//! 最后按选项排序，输出顺序与线程数无关。处理过程中的进度通过
+//! [`ProcessEvent`] 回调给调用方。
+
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use walkdir::WalkDir;
+
+use crate::config::OcrConfig;
+use crate::error::{InvoiceError, Result};
+use crate::export::{run_exporter, ExportReport};
+use crate::extractor::{
+    extract_image_info_with_rules, extract_invoice_info_with_rules, InvoiceFile, InvoiceInfo,
+    ProcessResult,
+};
+use crate::options::{ProcessOptions, SortOrder};
+use crate::rules::RuleSet;
+
+/// 批量处理过程中的事件，通过 [`ProcessOptionsBuilder::on_event`] 接收
+///
+/// 识别在多个线程中并行进行，事件可能从任意工作线程发出；
+/// `index` 是文件在遍历顺序中的序号，`completed` 是已处理完的文件数。
+///
+/// [`ProcessOptionsBuilder::on_event`]: crate::options::ProcessOptionsBuilder::on_event
+#[derive(Debug, Clone)]
+pub enum ProcessEvent {
+    /// 目录遍历完成
+    Scanned { total: usize },
+    /// 开始识别文件
+    FileStarted {
+        index: usize,
+        total: usize,
+        path: PathBuf,
+    },
+    /// 文件识别完成
+    FileFinished {
+        index: usize,
+        completed: usize,
+        total: usize,
+        invoice: InvoiceFile,
+    },
+    /// 文件处理失败，错误见 `invoice.error`
+    FileFailed {
+        index: usize,
+        completed: usize,
+        total: usize,
+        invoice: InvoiceFile,
+    },
+    /// 处理被取消，已完成的文件仍会导出
+    Cancelled { completed: usize, total: usize },
+    /// 导出器执行完成，失败时 `report.error` 不为空
+    ExportFinished { report: ExportReport },
+}
+
+/// 取消批量处理的令牌，克隆后可在其他线程调用 [`CancelToken::cancel`]
+///
+/// 取消后不再开始新的文件，正在识别的文件会处理完，已完成的结果照常导出。
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// 待识别的文件
+struct Job {
+    path: PathBuf,
+    invoice: InvoiceFile,
+}
+
+/// 处理所有发票文件，并交给选项中的每个导出器输出
+///
+/// 导出失败时仍返回识别结果，错误记录在 [`ProcessResult::exports`] 中。
+/// 中途取消时只导出已处理的文件，并设置 [`ProcessResult::cancelled`]。
+pub fn process_invoices(base_path: &Path, options: &ProcessOptions) -> Result<ProcessResult> {
+    let invoices = collect_invoices(base_path, options)?;
+    let cancelled = options.is_cancelled();
+    let exports = options
+        .exporters()
+        .iter()
+        .map(|exporter| {
+            let report = run_exporter(&invoices, exporter.as_ref());
+            options.emit(|| ProcessEvent::ExportFinished {
+                report: report.clone(),
+            });
+            report
+        })
+        .collect();
+
+    Ok(ProcessResult {
+        invoices,
+        exports,
+        cancelled,
+    })
+}
+
+/// 遍历目录识别所有发票文件，按选项排序
+///
+/// 中途取消时只返回已处理的文件。
+pub fn collect_invoices(base_path: &Path, options: &ProcessOptions) -> Result<Vec<InvoiceFile>> {
+    let jobs = scan(base_path, options)?;
+    options.emit(|| ProcessEvent::Scanned { total: jobs.len() });
+    let mut all_invoices = run_jobs(&jobs, options)?;
+    if options.is_cancelled() {
+        options.emit(|| ProcessEvent::Cancelled {
+            completed: all_invoices.len(),
+            total: jobs.len(),
+        });
+    }
+
+    // 从图片文件名提取金额
+    let filename_regex = &options.rules().image_filename_amount;
+    for inv in &mut all_invoices {
+        if inv.info.amount.is_empty() && !inv.filename.to_uppercase().ends_with(".PDF") {
+            if let Some(caps) = filename_regex.captures(&inv.filename.to_uppercase()) {
+                inv.info.amount = caps.get(1).unwrap().as_str().to_string();
+            }
+        }
+    }
+
+    // 排序
+    match options.sort {
+        SortOrder::Path => all_invoices.sort_by(|a, b| {
+            a.folder
+                .cmp(&b.folder)
+                .then_with(|| a.filename.cmp(&b.filename))
+        }),
+        SortOrder::FileName => all_invoices.sort_by(|a, b| {
+            a.filename
+                .cmp(&b.filename)
+                .then_with(|| a.folder.cmp(&b.folder))
+        }),
+        SortOrder::Unsorted => {}
+    }
+
+    Ok(all_invoices)
+}
+
+/// 遍历目录，按选项筛选出待处理的文件
+fn scan(base_path: &Path, options: &ProcessOptions) -> Result<Vec<Job>> {
+    if let Err(e) = std::fs::read_dir(base_path) {
+        return Err(InvoiceError::io(base_path, e));
+    }
+
+    let mut jobs = Vec::new();
+
+    let mut walker = WalkDir::new(base_path).follow_links(options.follow_links);
+    if let Some(depth) = options.max_depth {
+        walker = walker.max_depth(depth);
+    }
+
+    // 遍历目录
+    for entry in walker
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+    {
+        if options.is_cancelled() {
+            break;
+        }
+
+        let file_path = entry.path();
+        let file_name = entry.file_name().to_string_lossy();
+
+        // 跳过隐藏文件
+        if options.skip_hidden && file_name.starts_with('.') {
+            continue;
+        }
+
+        let file_ext = file_path
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or("")
+            .to_uppercase();
+
+        if !options.accepts_type(&file_ext) {
+            continue;
+        }
+
+        if !options.accepts_path(file_path.strip_prefix(base_path).unwrap_or(file_path)) {
+            continue;
+        }
+
+        let rel_path = entry
+            .path()
+            .parent()
+            .and_then(|p| p.strip_prefix(base_path).ok())
+            .and_then(|p| p.to_str())
+            .unwrap_or("")
+            .to_string();
+
+        jobs.push(Job {
+            path: file_path.to_path_buf(),
+            invoice: InvoiceFile {
+                folder: rel_path,
+                filename: file_name.to_string(),
+                file_type: file_ext,
+                info: InvoiceInfo::default(),
+                error: None,
+            },
+        });
+    }
+
+    Ok(jobs)
+}
+
+/// 用工作线程池识别所有文件，返回顺序与 `jobs` 一致，取消后未开始的文件不在结果中
+///
+/// 单个文件的panic已在 [`extract_isolated`] 中转为该文件的错误；
+/// 工作线程本身panic（如事件回调panic）时返回 [`InvoiceError::WorkerPanicked`]。
+fn run_jobs(jobs: &[Job], options: &ProcessOptions) -> Result<Vec<InvoiceFile>> {
+    let completed = AtomicUsize::new(0);
+    let run = |idx: usize, job: &Job| {
+        options.emit(|| ProcessEvent::FileStarted {
+            index: idx,
+            total: jobs.len(),
+            path: job.path.clone(),
+        });
+        let invoice = process_job(job, options);
+        let completed = completed.fetch_add(1, Ordering::Relaxed) + 1;
+        options.emit(|| {
+            let (index, total, invoice) = (idx, jobs.len(), invoice.clone());
+            if invoice.is_failed() {
+                ProcessEvent::FileFailed { index, completed, total, invoice }
+            } else {
+                ProcessEvent::FileFinished { index, completed, total, invoice }
+            }
+        });
+        invoice
+    };
+
+    let workers = options.workers().min(jobs.len()).max(1);
+    if workers == 1 {
+        return Ok(jobs
+            .iter()
+            .enumerate()
+            .take_while(|_| !options.is_cancelled())
+            .map(|(idx, job)| run(idx, job))
+            .collect());
+    }
+
+    // 工作线程依次领取下一个文件的序号
+    let next = AtomicUsize::new(0);
+    let results: Vec<_> = thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut finished = Vec::new();
+                    while !options.is_cancelled() {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(job) = jobs.get(idx) else {
+                            break;
+                        };
+                        finished.push((idx, run(idx, job)));
+                    }
+                    finished
+                })
+            })
+            .collect();
+
+        // 等待所有线程结束后再处理panic，其余线程领取的文件照常完成
+        handles.into_iter().map(|handle| handle.join()).collect()
+    });
+
+    let mut done = Vec::new();
+    for result in results {
+        done.extend(result.map_err(InvoiceError::worker_panicked)?);
+    }
+    done.sort_by_key(|(idx, _)| *idx);
+    Ok(done.into_iter().map(|(_, invoice)| invoice).collect())
+}
+
+/// 识别单个文件，PDF和发票图片会解析内容，其他类型只列出文件
+fn process_job(job: &Job, options: &ProcessOptions) -> InvoiceFile {
+    let mut invoice = job.invoice.clone();
+
+    if invoice.is_pdf() || invoice.is_image() {
+        match extract_isolated(&job.path, options) {
+            Ok(info) => invoice.info = info,
+            Err(e) => invoice.error = Some(e),
+        }
+    }
+
+    invoice
+}
+
+/// 隔离识别单个文件，panic和超时都转为该文件的错误，不影响其他文件
+///
+/// 设置了超时时在单独的线程中解析；超时后不再等待，该线程（以及它启动的 Tesseract 进程）
+/// 在后台运行到结束，批量处理返回后仍可能在运行。
+fn extract_isolated(path: &Path, options: &ProcessOptions) -> Result<InvoiceInfo> {
+    let Some(timeout) = options.timeout() else {
+        return catch_panic(path, || {
+            extract_file(path, options.buyer_keyword(), options.rules(), options.ocr())
+        });
+    };
+
+    let (tx, rx) = mpsc::channel();
+    let thread_path = path.to_path_buf();
+    let buyer_keyword = options.buyer_keyword.clone();
+    let rules = options.rules.clone();
+    let ocr = options.ocr.clone();
+    thread::Builder::new()
+        .name("invoice-extract".to_string())
+        .spawn(move || {
+            let result = catch_panic(&thread_path, || {
+                let rules = match &rules {
+                    Some(rules) => rules,
+                    None => RuleSet::shared(),
+                };
+                extract_file(&thread_path, buyer_keyword.as_deref(), rules, &ocr)
+            });
+            let _ = tx.send(result);
+        })
+        .map_err(|e| InvoiceError::io(path, e))?;
+
+    match rx.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(mpsc::RecvTimeoutError::Timeout) => Err(InvoiceError::Timeout {
+            path: path.to_path_buf(),
+            timeout,
+        }),
+        Err(mpsc::RecvTimeoutError::Disconnected) => Err(InvoiceError::Panicked {
+            path: path.to_path_buf(),
+            message: "识别线程意外退出".to_string(),
+        }),
+    }
+}
+
+/// 按扩展名识别：PDF解析文本和二维码，图片识别二维码（启用 `ocr` 功能时还识别图片和扫描件中的文字）
+fn extract_file(
+    path: &Path,
+    buyer_keyword: Option<&str>,
+    rules: &RuleSet,
+    ocr: &OcrConfig,
+) -> Result<InvoiceInfo> {
+    let is_pdf = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"));
+    if is_pdf {
+        extract_invoice_info_with_rules(path, buyer_keyword, rules, ocr)
+    } else {
+        extract_image_info_with_rules(path, buyer_keyword, rules, ocr)
+    }
+}
+
+fn catch_panic<T>(path: &Path, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|payload| Err(InvoiceError::panicked(path, payload)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 临时目录中的若干个文本文件，测试结束时删除
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn with_files(name: &str, count: usize) -> Self {
+            let dir = std::env::temp_dir().join(format!("invoice-batch-{}-{}", name, std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            for i in 0..count {
+                std::fs::write(dir.join(format!("{}.txt", i)), "").unwrap();
+            }
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn collects_files_in_order() {
+        let dir = TempDir::with_files("order", 5);
+        let options = ProcessOptions::builder().file_types(["txt"]).workers(3).build().unwrap();
+        let invoices = collect_invoices(&dir.0, &options).unwrap();
+        let names: Vec<&str> = invoices.iter().map(|inv| inv.filename.as_str()).collect();
+        assert_eq!(names, ["0.txt", "1.txt", "2.txt", "3.txt", "4.txt"]);
+    }
+
+    #[test]
+    fn worker_panic_is_an_error() {
+        let dir = TempDir::with_files("panic", 4);
+        let options = ProcessOptions::builder()
+            .file_types(["txt"])
+            .workers(2)
+            .on_event(|event| {
+                if matches!(event, ProcessEvent::FileFinished { index: 1, .. }) {
+                    panic!("回调出错");
+                }
+            })
+            .build()
+            .unwrap();
+        match collect_invoices(&dir.0, &options) {
+            Err(InvoiceError::WorkerPanicked { message }) => assert_eq!(message, "回调出错"),
+            other => panic!("{:?}", other.map(|invoices| invoices.len())),
+        }
+    }
+}
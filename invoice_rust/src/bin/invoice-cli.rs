@@ -0,0 +1,362 @@
+//! 发票识别命令行工具
+//!
+//! 用于构建服务器、定时任务等无界面环境。
+//!
+//! 退出码：0 全部成功；1 运行错误或导出失败；2 有文件处理失败；3 有发票缺少必填字段。
+
+use clap::{Parser, Subcommand};
+use invoice_extractor::{
+    extract_image_info_with_rules, extract_invoice_info_with_rules, process_invoices, Config,
+    CsvEncoding, CsvExporter, CsvOptions, InvoiceFile, JsonExporter, ProcessOptions, QrMismatch,
+    RuleSet, SortOrder, TaxIdCheck, XlsxExporter, XlsxOptions,
+};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+const EXIT_ERROR: u8 = 1;
+const EXIT_FAILED: u8 = 2;
+const EXIT_INCOMPLETE: u8 = 3;
+
+#[derive(Parser)]
+#[command(name = "invoice-cli", version, about = "发票识别工具（命令行版）")]
+struct Cli {
+    /// 配置文件（默认读取程序所在目录的 invoice-extractor.toml 和用户配置目录下的配置）
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)] // 只在启动时解析一次
+enum Command {
+    /// 识别单个PDF发票或发票图片并输出字段
+    Extract {
+        /// PDF发票文件，或PNG、JPG发票图片
+        file: PathBuf,
+        /// 购买方关键词
+        #[arg(long)]
+        buyer: Option<String>,
+    },
+    /// 批量识别目录下的发票并生成Excel
+    Batch {
+        /// 发票目录
+        dir: PathBuf,
+        /// 购买方关键词
+        #[arg(long)]
+        buyer: Option<String>,
+        /// 输出Excel文件（默认为发票目录下的"发票清单.xlsx"）
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Excel中增加"明细"工作表，每个明细行一行
+        #[arg(long)]
+        line_items: bool,
+        /// 同时导出整批结果为JSON文件
+        #[arg(long)]
+        json: Option<PathBuf>,
+        /// 同时导出为JSON Lines文件（每行一张发票）
+        #[arg(long)]
+        jsonl: Option<PathBuf>,
+        /// 同时导出为CSV文件
+        #[arg(long)]
+        csv: Option<PathBuf>,
+        /// CSV分隔符
+        #[arg(long, default_value_t = ',', value_parser = parse_delimiter)]
+        csv_delimiter: char,
+        /// CSV编码：utf8、utf8-bom、gbk
+        #[arg(long, default_value = "utf8-bom")]
+        csv_encoding: CsvEncoding,
+        /// 最大遍历深度（1 表示不进入子文件夹）
+        #[arg(long)]
+        max_depth: Option<usize>,
+        /// 处理的文件类型，逗号分隔（默认为 pdf,png,jpg,jpeg）
+        #[arg(long, value_delimiter = ',')]
+        types: Vec<String>,
+        /// 只处理匹配的路径（相对发票目录，如 "2024-*/**"），可重复
+        #[arg(long)]
+        include: Vec<String>,
+        /// 跳过匹配的路径（如 "**/作废/**"），可重复
+        #[arg(long)]
+        exclude: Vec<String>,
+        /// 跟随符号链接
+        #[arg(long)]
+        follow_links: bool,
+        /// 处理以 . 开头的隐藏文件
+        #[arg(long)]
+        include_hidden: bool,
+        /// 排序方式：path（文件夹+文件名）、name、none
+        #[arg(long, default_value = "path")]
+        sort: SortOrder,
+        /// 并行识别的线程数（默认为CPU核数）
+        #[arg(long, short = 'j', default_value_t = 0)]
+        jobs: usize,
+        /// 单个文件的识别时间限制（秒），超时的文件记为失败，0 表示不限（默认取配置文件中的 timeout）
+        #[arg(long)]
+        timeout: Option<u64>,
+    },
+    /// 输出当前生效的配置，可作为配置文件的模板
+    Config,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let config = match Config::load_with(cli.config.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+
+    match cli.command {
+        Command::Extract { file, buyer } => run_extract(&file, buyer.as_deref(), &config),
+        Command::Batch {
+            dir,
+            buyer,
+            out,
+            line_items,
+            json,
+            jsonl,
+            csv,
+            csv_delimiter,
+            csv_encoding,
+            max_depth,
+            types,
+            include,
+            exclude,
+            follow_links,
+            include_hidden,
+            sort,
+            jobs,
+            timeout,
+        } => {
+            let out = out.unwrap_or_else(|| dir.join("发票清单.xlsx"));
+            let mut builder = ProcessOptions::builder()
+                .config(&config)
+                .follow_links(follow_links)
+                .skip_hidden(!include_hidden)
+                .sort(sort)
+                .workers(jobs)
+                .exporter(XlsxExporter::with_options(out, XlsxOptions { line_items }));
+            if let Some(buyer) = buyer {
+                builder = builder.buyer_keyword(buyer);
+            }
+            if !types.is_empty() {
+                builder = builder.file_types(types);
+            }
+            if let Some(depth) = max_depth {
+                builder = builder.max_depth(depth);
+            }
+            if let Some(secs) = timeout {
+                builder = builder.timeout(Duration::from_secs(secs));
+            }
+            for pattern in include {
+                builder = builder.include(pattern);
+            }
+            for pattern in exclude {
+                builder = builder.exclude(pattern);
+            }
+            if let Some(path) = json {
+                builder = builder.exporter(JsonExporter::new(path));
+            }
+            if let Some(path) = jsonl {
+                builder = builder.exporter(JsonExporter::lines(path));
+            }
+            if let Some(path) = csv {
+                let options = CsvOptions {
+                    delimiter: csv_delimiter as u8,
+                    encoding: csv_encoding,
+                };
+                builder = builder.exporter(CsvExporter::new(path, options));
+            }
+
+            match builder.build() {
+                Ok(options) => run_batch(&dir, &options),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    ExitCode::from(EXIT_ERROR)
+                }
+            }
+        }
+        Command::Config => {
+            for path in Config::search_paths(cli.config.as_deref()) {
+                let state = if path.is_file() { "已读取" } else { "不存在" };
+                println!("# {}（{}）", path.display(), state);
+            }
+            println!();
+            print!("{}", config.to_toml());
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+fn run_extract(file: &Path, buyer: Option<&str>, config: &Config) -> ExitCode {
+    let rules = match RuleSet::from_config(&config.rules) {
+        Ok(rules) => rules,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+    let default_buyer = &config.defaults.buyer_keyword;
+    let buyer = buyer.or((!default_buyer.is_empty()).then_some(default_buyer.as_str()));
+
+    let is_pdf = file
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"));
+    let result = if is_pdf {
+        extract_invoice_info_with_rules(file, buyer, &rules, &config.ocr)
+    } else {
+        extract_image_info_with_rules(file, buyer, &rules, &config.ocr)
+    };
+    let info = match result {
+        Ok(info) => info,
+        Err(e) => {
+            eprintln!("处理失败: {}", e);
+            return ExitCode::from(EXIT_FAILED);
+        }
+    };
+
+    println!("发票类型: {}", info.invoice_type);
+    println!("发票号码: {}", info.invoice_number);
+    if !info.invoice_code.is_empty() {
+        println!("发票代码: {}", info.invoice_code);
+    }
+    if !info.check_code.is_empty() {
+        println!("校验码: {}", info.check_code);
+    }
+    println!("开票日期: {}", info.invoice_date);
+    println!("购买方: {}", info.buyer);
+    println!("购买方税号: {}{}", info.buyer_tax_number, tax_mark(info.buyer_tax_number_check));
+    println!("销售方: {}", info.seller);
+    println!("销售方税号: {}{}", info.seller_tax_number, tax_mark(info.seller_tax_number_check));
+    println!("项目内容: {}", info.item_content);
+    println!("金额: {}", info.amount_without_tax);
+    println!("税率: {}", info.tax_rate);
+    println!("税额: {}", info.tax_amount);
+    println!("价税合计: {}", info.amount);
+    println!("大写金额: {}", info.amount_in_words);
+    println!("备注: {}", info.remark);
+    if let Some(qr) = &info.qr {
+        println!("二维码: {}", qr.payload);
+    }
+    if info.scanned {
+        eprintln!("扫描件: 没有可用的文本层，字段来自文字识别或二维码");
+    }
+    if !info.ocr_error.is_empty() {
+        eprintln!("{}（字段只取自二维码）", info.ocr_error);
+    }
+
+    if info.amount_mismatch {
+        eprintln!("金额校验不一致: 金额 + 税额 ≠ 价税合计");
+    }
+    if info.amount_in_words_mismatch() {
+        eprintln!("大写金额不符: {} ≠ {}", info.amount_in_words, info.amount);
+    }
+    if !info.qr_mismatch.is_empty() {
+        eprintln!("二维码与文本不一致: {}", mismatch_text(&info.qr_mismatch));
+    }
+    let missing = info.missing_fields();
+    if !missing.is_empty() {
+        eprintln!("缺少字段: {}", missing.join("、"));
+        return ExitCode::from(EXIT_INCOMPLETE);
+    }
+    ExitCode::SUCCESS
+}
+
+fn run_batch(dir: &Path, options: &ProcessOptions) -> ExitCode {
+    let result = match process_invoices(dir, options) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("处理失败: {}", e);
+            return ExitCode::from(EXIT_ERROR);
+        }
+    };
+
+    let summary = result.summary();
+    println!("{}", summary);
+    println!();
+
+    for report in &result.exports {
+        match &report.error {
+            None => println!("{}已保存: {}", report.format, report.path.display()),
+            Some(e) => eprintln!("导出{}失败: {}", report.format, e),
+        }
+    }
+
+    for inv in result.invoices.iter().filter(|inv| inv.is_pdf() || inv.is_image()) {
+        let path = display_path(inv);
+        if let Some(e) = &inv.error {
+            eprintln!("失败: {} ({})", path, e);
+        } else {
+            let missing = inv.info.missing_fields();
+            if !missing.is_empty() {
+                eprintln!("缺少字段: {} ({})", path, missing.join("、"));
+            }
+            if inv.info.amount_mismatch {
+                eprintln!(
+                    "金额校验不一致: {} ({} + {} ≠ {})",
+                    path, inv.info.amount_without_tax, inv.info.tax_amount, inv.info.amount
+                );
+            }
+            if inv.info.amount_in_words_mismatch() {
+                eprintln!(
+                    "大写金额不符: {} ({} ≠ {})",
+                    path, inv.info.amount_in_words, inv.info.amount
+                );
+            }
+            if !inv.info.qr_mismatch.is_empty() {
+                eprintln!("二维码与文本不一致: {} ({})", path, mismatch_text(&inv.info.qr_mismatch));
+            }
+            if inv.info.scanned {
+                eprintln!("扫描件: {}", path);
+            }
+            if !inv.info.ocr_error.is_empty() {
+                eprintln!("{}（字段只取自二维码）", inv.info.ocr_error);
+            }
+        }
+    }
+
+    if result.exports.iter().any(|report| !report.is_ok()) {
+        ExitCode::from(EXIT_ERROR)
+    } else if summary.failed > 0 {
+        ExitCode::from(EXIT_FAILED)
+    } else if summary.incomplete > 0 {
+        ExitCode::from(EXIT_INCOMPLETE)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn parse_delimiter(s: &str) -> Result<char, String> {
+    let s = if s == "\\t" { "\t" } else { s };
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii() => Ok(c),
+        _ => Err("分隔符必须是单个ASCII字符（制表符可写作 \\t）".to_string()),
+    }
+}
+
+/// 税号校验未通过或无法校验时的提示
+fn tax_mark(check: Option<TaxIdCheck>) -> &'static str {
+    match check {
+        Some(TaxIdCheck::Invalid) => "（校验未通过）",
+        Some(TaxIdCheck::Unverifiable) => "（无法校验）",
+        Some(TaxIdCheck::Valid) | None => "",
+    }
+}
+
+/// 不一致的字段及两边的值
+fn mismatch_text(mismatches: &[QrMismatch]) -> String {
+    let details: Vec<String> = mismatches.iter().map(|m| m.to_string()).collect();
+    details.join("；")
+}
+
+fn display_path(inv: &InvoiceFile) -> String {
+    if inv.folder.is_empty() {
+        inv.filename.clone()
+    } else {
+        format!("{}/{}", inv.folder, inv.filename)
+    }
+}
@@ -0,0 +1,206 @@
+//! 配置文件
+//!
+//! 识别规则中与地区、行业相关的部分（销售方关键词、排除的文本行、金额上限）和
+//! 批量处理的默认选项写在 TOML 配置文件中，GUI和命令行共用，修改后无需重新编译。
+//!
+//! 按以下顺序读取，后读取的设置覆盖前面的同名设置（列表整体替换）：
+//!
+//! 1. 内置默认值
+//! 2. 程序所在目录的 `invoice-extractor.toml`（命令行可用 `--config` 指定其他文件）
+//! 3. 用户配置目录下的 `invoice-extractor/config.toml`，
+//!    Windows 为 `%APPDATA%\invoice-extractor\config.toml`，
+//!    macOS 为 `~/Library/Application Support/invoice-extractor/config.toml`，
+//!    Linux 为 `~/.config/invoice-extractor/config.toml`
+//!
+//! ```toml
+//! [rules]
+//! seller_keywords = ["有限公司", "加油站", "药房"]
+//! exclude_lines = ['\*[^*]+\*', "国家税务总局|发票监制|浙江省税务局"]
+//! max_amount = 50000000
+//!
+//! [defaults]
+//! buyer_keyword = "某某公司"
+//! exclude = ["**/作废/**"]
+//! timeout = 120
+//!
+//! [ocr]
+//! command = 'C:\Program Files\Tesseract-OCR\tesseract.exe'
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{InvoiceError, Result};
+use crate::options::DEFAULT_FILE_TYPES;
+use crate::rules::{CONTEXT_KEYWORDS, EXCLUDE_PATTERNS, MAX_AMOUNT, SELLER_KEYWORDS};
+
+/// 程序所在目录的配置文件名
+pub const CONFIG_FILE_NAME: &str = "invoice-extractor.toml";
+
+/// 配置文件内容，未写出的设置使用内置默认值
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub rules: RuleConfig,
+    pub defaults: DefaultsConfig,
+    pub ocr: OcrConfig,
+}
+
+/// 识别规则
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleConfig {
+    /// 含有这些关键词的文本行视为公司名称
+    pub seller_keywords: Vec<String>,
+    /// 在税号附近查找销售方名称时使用的关键词
+    pub context_keywords: Vec<String>,
+    /// 匹配这些正则表达式的文本行不会是公司名称（表头、监制章、金额行等）
+    pub exclude_lines: Vec<String>,
+    /// 金额上限，超过的¥数值视为误识别
+    pub max_amount: f64,
+}
+
+impl Default for RuleConfig {
+    fn default() -> Self {
+        let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+        Self {
+            seller_keywords: strings(&SELLER_KEYWORDS),
+            context_keywords: strings(&CONTEXT_KEYWORDS),
+            exclude_lines: strings(&EXCLUDE_PATTERNS),
+            max_amount: MAX_AMOUNT,
+        }
+    }
+}
+
+/// 批量处理的默认选项，GUI和命令行中设置的值优先
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DefaultsConfig {
+    /// 购买方关键词，空字符串表示自动识别
+    pub buyer_keyword: String,
+    /// 处理的文件类型（扩展名）
+    pub file_types: Vec<String>,
+    /// 最大遍历深度，不写表示不限
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+    /// 跳过的路径（相对发票目录），如 `**/作废/**`
+    pub exclude: Vec<String>,
+    /// 单个文件的识别时间限制（秒），0 表示不限；不写时命令行不限，GUI为60秒
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u64>,
+}
+
+impl Default for DefaultsConfig {
+    fn default() -> Self {
+        Self {
+            buyer_keyword: String::new(),
+            file_types: DEFAULT_FILE_TYPES
+                .iter()
+                .map(|t| t.to_lowercase())
+                .collect(),
+            max_depth: None,
+            exclude: Vec::new(),
+            timeout: None,
+        }
+    }
+}
+
+/// 发票照片和扫描件PDF的文字识别，只在编译时启用 `ocr` 功能后生效
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OcrConfig {
+    /// Tesseract 命令，不在 PATH 中时写完整路径
+    pub command: String,
+    /// 识别语言，需要安装对应的语言包
+    pub languages: String,
+    /// 其他命令行参数
+    pub args: Vec<String>,
+    /// 识别扫描件PDF（没有可用文本层的PDF）中的页面图片
+    pub scanned_pdfs: bool,
+}
+
+impl Default for OcrConfig {
+    fn default() -> Self {
+        Self {
+            command: "tesseract".to_string(),
+            languages: "chi_sim+eng".to_string(),
+            // 保留较宽的间距，用于区分汉字间多余的空格和左右并排的区块
+            args: vec!["-c".to_string(), "preserve_interword_spaces=1".to_string()],
+            scanned_pdfs: true,
+        }
+    }
+}
+
+impl Config {
+    /// 读取程序所在目录和用户配置目录下的配置文件，文件不存在时跳过
+    pub fn load() -> Result<Self> {
+        Self::load_with(None)
+    }
+
+    /// 读取配置，`path` 指定时代替程序所在目录的配置文件，且文件必须存在
+    pub fn load_with(path: Option<&Path>) -> Result<Self> {
+        let mut merged = toml::Table::new();
+        let mut last = PathBuf::from(CONFIG_FILE_NAME);
+        for file in Self::search_paths(path) {
+            if path != Some(file.as_path()) && !file.is_file() {
+                continue;
+            }
+            merge(&mut merged, read_table(&file)?);
+            last = file;
+        }
+        merged.try_into().map_err(|e| invalid(&last, e))
+    }
+
+    /// 依次读取的配置文件路径（不一定存在），`path` 的含义同 [`load_with`](Self::load_with)
+    pub fn search_paths(path: Option<&Path>) -> Vec<PathBuf> {
+        let base = match path {
+            Some(path) => Some(path.to_path_buf()),
+            None => program_config_path(),
+        };
+        base.into_iter().chain(user_config_path()).collect()
+    }
+
+    /// 输出为 TOML 文本，可作为配置文件的模板
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("配置总能序列化为TOML")
+    }
+}
+
+/// 程序所在目录的配置文件
+fn program_config_path() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    Some(exe.parent()?.join(CONFIG_FILE_NAME))
+}
+
+/// 用户配置目录下的配置文件
+fn user_config_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("invoice-extractor").join("config.toml"))
+}
+
+/// 读取一个配置文件，并检查其中的设置名和类型
+fn read_table(path: &Path) -> Result<toml::Table> {
+    let text = fs::read_to_string(path).map_err(|e| InvoiceError::io(path, e))?;
+    let table: toml::Table = text.parse().map_err(|e| invalid(path, e))?;
+    Config::deserialize(table.clone()).map_err(|e| invalid(path, e))?;
+    Ok(table)
+}
+
+fn invalid(path: &Path, err: toml::de::Error) -> InvoiceError {
+    InvoiceError::InvalidConfig {
+        path: path.to_path_buf(),
+        message: err.to_string().trim_end().to_string(),
+    }
+}
+
+/// 按表合并，`overlay` 中的值覆盖 `base` 中的同名值
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) => merge(base, overlay),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
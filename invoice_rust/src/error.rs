@@ -0,0 +1,153 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 发票处理错误
+///
+/// 文件相关的错误都带有出错文件的路径，底层错误用 `Arc` 包装以便随结果一起克隆。
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum InvoiceError {
+    /// PDF文件损坏或格式无法解析
+    #[error("PDF无法解析: {path}: {message}")]
+    PdfUnreadable { path: PathBuf, message: String },
+
+    /// PDF已加密且无法用空密码解密
+    #[error("PDF已加密: {path}")]
+    PdfEncrypted { path: PathBuf },
+
+    /// 扫描件PDF：没有可用的文本层，文字识别和二维码也都没有结果
+    #[error("扫描件PDF，没有可用的文本层: {path}")]
+    NoTextLayer { path: PathBuf },
+
+    /// 图片文件损坏或格式不支持
+    #[error("图片无法读取: {path}: {message}")]
+    ImageUnreadable { path: PathBuf, message: String },
+
+    /// 图片文字识别失败（如未安装 Tesseract）
+    #[error("文字识别失败: {path}: {message}")]
+    Ocr { path: PathBuf, message: String },
+
+    /// 识别过程中发生panic（如畸形PDF触发解析库的内部错误）
+    #[error("识别时发生内部错误: {path}: {message}")]
+    Panicked { path: PathBuf, message: String },
+
+    /// 批量处理的工作线程在识别文件之外发生panic（如事件回调panic）
+    #[error("批量处理的工作线程意外退出: {message}")]
+    WorkerPanicked { message: String },
+
+    /// 识别超过单个文件的时间限制
+    #[error("识别超时: {path}（超过 {} 秒）", .timeout.as_secs_f64())]
+    Timeout { path: PathBuf, timeout: Duration },
+
+    /// 文件读写错误
+    #[error("文件读写失败: {path}: {source}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: Arc<std::io::Error>,
+    },
+
+    /// Excel写入错误
+    #[error("保存Excel失败: {path}: {source}")]
+    Excel {
+        path: PathBuf,
+        #[source]
+        source: Arc<rust_xlsxwriter::XlsxError>,
+    },
+
+    /// 包含/排除规则无效
+    #[error("路径匹配规则无效: {pattern}: {message}")]
+    InvalidGlob { pattern: String, message: String },
+
+    /// 配置文件无法解析，或含有未知的设置
+    #[error("配置文件无效: {path}: {message}")]
+    InvalidConfig { path: PathBuf, message: String },
+
+    /// 配置中的识别规则不是有效的正则表达式
+    #[error("识别规则无效: {pattern}: {message}")]
+    InvalidRule { pattern: String, message: String },
+}
+
+impl InvoiceError {
+    /// 出错文件的路径
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::PdfUnreadable { path, .. }
+            | Self::PdfEncrypted { path }
+            | Self::NoTextLayer { path }
+            | Self::ImageUnreadable { path, .. }
+            | Self::Ocr { path, .. }
+            | Self::Panicked { path, .. }
+            | Self::Timeout { path, .. }
+            | Self::Io { path, .. }
+            | Self::Excel { path, .. }
+            | Self::InvalidConfig { path, .. } => Some(path),
+            Self::InvalidGlob { .. } | Self::InvalidRule { .. } | Self::WorkerPanicked { .. } => None,
+        }
+    }
+
+    pub(crate) fn io(path: &Path, source: std::io::Error) -> Self {
+        Self::Io {
+            path: path.to_path_buf(),
+            source: Arc::new(source),
+        }
+    }
+
+    pub(crate) fn excel(path: &Path, source: rust_xlsxwriter::XlsxError) -> Self {
+        Self::Excel {
+            path: path.to_path_buf(),
+            source: Arc::new(source),
+        }
+    }
+
+    /// 将捕获的panic转为错误，保留panic信息
+    pub(crate) fn panicked(path: &Path, payload: Box<dyn std::any::Any + Send>) -> Self {
+        Self::Panicked {
+            path: path.to_path_buf(),
+            message: panic_message(payload),
+        }
+    }
+
+    /// 将工作线程的panic转为整批处理的错误
+    pub(crate) fn worker_panicked(payload: Box<dyn std::any::Any + Send>) -> Self {
+        Self::WorkerPanicked {
+            message: panic_message(payload),
+        }
+    }
+
+    /// 将 pdf-extract 的错误归类
+    pub(crate) fn from_pdf(path: &Path, err: pdf_extract::OutputError) -> Self {
+        use pdf_extract::OutputError;
+
+        match err {
+            OutputError::IoError(e) | OutputError::PdfError(lopdf::Error::IO(e)) => {
+                Self::io(path, e)
+            }
+            OutputError::PdfError(lopdf::Error::Decryption(_)) => Self::PdfEncrypted {
+                path: path.to_path_buf(),
+            },
+            other => Self::PdfUnreadable {
+                path: path.to_path_buf(),
+                message: other.to_string(),
+            },
+        }
+    }
+}
+
+/// panic信息，不是字符串时为"未知错误"
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "未知错误".to_string())
+}
+
+/// 导出时序列化为错误信息字符串
+impl serde::Serialize for InvoiceError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, InvoiceError>;
@@ -0,0 +1,187 @@
+//! CSV 导出
+//!
+//! 旧版Excel打开CSV时需要GBK或带BOM的UTF-8才能正确显示中文，因此编码可选。
+
+use std::path::Path;
+use std::str::FromStr;
+
+use super::{amount_check_text, qr_check_text, tax_check_text, HEADERS};
+use crate::error::{InvoiceError, Result};
+use crate::extractor::InvoiceFile;
+
+/// CSV文件编码
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CsvEncoding {
+    /// UTF-8，无BOM
+    Utf8,
+    /// UTF-8，带BOM（Excel可直接识别）
+    #[default]
+    Utf8Bom,
+    /// GBK（旧版Excel、WPS），含有GBK无法表示的字符（如部分生僻字、emoji）时导出失败
+    Gbk,
+}
+
+impl FromStr for CsvEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().replace(['-', '_'], "").as_str() {
+            "utf8" => Ok(Self::Utf8),
+            "utf8bom" => Ok(Self::Utf8Bom),
+            "gbk" | "gb2312" | "gb18030" => Ok(Self::Gbk),
+            _ => Err(format!("不支持的编码: {}（可选 utf8、utf8-bom、gbk）", s)),
+        }
+    }
+}
+
+/// CSV导出选项
+#[derive(Debug, Clone)]
+pub struct CsvOptions {
+    /// 分隔符，必须是ASCII字符
+    pub delimiter: u8,
+    pub encoding: CsvEncoding,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            encoding: CsvEncoding::default(),
+        }
+    }
+}
+
+/// 生成CSV文件，列与Excel一致
+pub fn generate_csv(invoices: &[InvoiceFile], output_path: &Path, options: &CsvOptions) -> Result<()> {
+    let io_err = |e| InvoiceError::io(output_path, e);
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .from_writer(Vec::new());
+
+    writer.write_record(HEADERS).map_err(|e| io_err(e.into()))?;
+    for (idx, inv) in invoices.iter().enumerate() {
+        writer
+            .write_record([
+                (idx + 1).to_string(),
+                inv.folder.clone(),
+                inv.filename.clone(),
+                inv.info.invoice_type.label().to_string(),
+                inv.info.invoice_number.clone(),
+                inv.info.invoice_code.clone(),
+                inv.info.check_code.clone(),
+                inv.info.invoice_date.clone(),
+                inv.info.buyer.clone(),
+                inv.info.buyer_tax_number.clone(),
+                tax_check_text(inv.info.buyer_tax_number_check).to_string(),
+                inv.info.seller.clone(),
+                inv.info.seller_tax_number.clone(),
+                tax_check_text(inv.info.seller_tax_number_check).to_string(),
+                inv.info.item_content.clone(),
+                inv.info.amount_without_tax.clone(),
+                inv.info.tax_rate.clone(),
+                inv.info.tax_amount.clone(),
+                inv.info.amount.clone(),
+                amount_check_text(&inv.info).to_string(),
+                qr_check_text(&inv.info),
+                inv.remark_text(),
+            ])
+            .map_err(|e| io_err(e.into()))?;
+    }
+
+    let data = writer.into_inner().map_err(|e| io_err(e.into_error()))?;
+    // 非ASCII分隔符会产生非法的UTF-8
+    let text = String::from_utf8(data)
+        .map_err(|e| io_err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))?;
+
+    let bytes = match options.encoding {
+        CsvEncoding::Utf8 => text.into_bytes(),
+        CsvEncoding::Utf8Bom => {
+            let mut bytes = b"\xEF\xBB\xBF".to_vec();
+            bytes.extend_from_slice(text.as_bytes());
+            bytes
+        }
+        CsvEncoding::Gbk => {
+            let (bytes, _, had_errors) = encoding_rs::GBK.encode(&text);
+            if had_errors {
+                // 替换为 &#NNNN; 后写入会悄悄改掉公司名称等字段，所以整个文件不导出
+                let message = format!(
+                    "GBK编码无法表示字符 {}，请改用UTF-8编码",
+                    unmappable_chars(&text).join(" ")
+                );
+                return Err(io_err(std::io::Error::new(std::io::ErrorKind::InvalidData, message)));
+            }
+            bytes.into_owned()
+        }
+    };
+
+    std::fs::write(output_path, bytes).map_err(io_err)
+}
+
+/// GBK无法表示的字符，按出现顺序去重
+fn unmappable_chars(text: &str) -> Vec<String> {
+    let mut chars: Vec<String> = Vec::new();
+    let mut buf = [0u8; 4];
+    for c in text.chars().filter(|c| !c.is_ascii()) {
+        let c = c.encode_utf8(&mut buf);
+        if encoding_rs::GBK.encode(c).2 && !chars.iter().any(|seen| seen == c) {
+            chars.push(c.to_string());
+        }
+    }
+    chars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractor::InvoiceInfo;
+
+    fn invoice(seller: &str) -> InvoiceFile {
+        InvoiceFile {
+            filename: "发票.pdf".to_string(),
+            file_type: "PDF".to_string(),
+            info: InvoiceInfo {
+                seller: seller.to_string(),
+                ..InvoiceInfo::default()
+            },
+            ..InvoiceFile::default()
+        }
+    }
+
+    fn output(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("invoice-csv-{}-{}.csv", name, std::process::id()))
+    }
+
+    #[test]
+    fn writes_gbk() {
+        let path = output("gbk");
+        let options = CsvOptions {
+            encoding: CsvEncoding::Gbk,
+            ..CsvOptions::default()
+        };
+        generate_csv(&[invoice("上海晨光文具贸易有限公司")], &path, &options).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let (text, _, had_errors) = encoding_rs::GBK.decode(&bytes);
+        assert!(!had_errors);
+        assert!(text.contains("上海晨光文具贸易有限公司"));
+    }
+
+    #[test]
+    fn refuses_gbk_for_unmappable_characters() {
+        let path = output("unmappable");
+        let options = CsvOptions {
+            encoding: CsvEncoding::Gbk,
+            ..CsvOptions::default()
+        };
+        let invoices = [invoice("𠮷野家餐饮有限公司"), invoice("咖啡☕𠮷")];
+        let err = generate_csv(&invoices, &path, &options).unwrap_err();
+        assert!(!path.exists());
+        let message = err.to_string();
+        assert!(message.contains("𠮷 ☕"), "{}", message);
+
+        // UTF-8 可以表示所有字符
+        generate_csv(&invoices, &path, &CsvOptions::default()).unwrap();
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,69 @@
+//! JSON / JSON Lines 导出
+//!
+//! 字段定义见 `docs/JSON_SCHEMA.md`，字段有不兼容变化时递增 [`SCHEMA_VERSION`]。
+
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::error::{InvoiceError, Result};
+use crate::extractor::InvoiceFile;
+
+/// JSON导出格式版本
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// 整批导出的顶层对象
+#[derive(Serialize)]
+struct JsonBatch<'a> {
+    schema_version: u32,
+    invoices: &'a [InvoiceFile],
+}
+
+/// JSON Lines 中的一行
+#[derive(Serialize)]
+struct JsonRecord<'a> {
+    schema_version: u32,
+    #[serde(flatten)]
+    invoice: &'a InvoiceFile,
+}
+
+/// 将整批发票写入一个JSON文件
+pub fn generate_json(invoices: &[InvoiceFile], output_path: &Path) -> Result<()> {
+    let batch = JsonBatch {
+        schema_version: SCHEMA_VERSION,
+        invoices,
+    };
+
+    let mut writer = create(output_path)?;
+    serde_json::to_writer_pretty(&mut writer, &batch)
+        .map_err(|e| InvoiceError::io(output_path, e.into()))?;
+    writer
+        .flush()
+        .map_err(|e| InvoiceError::io(output_path, e))
+}
+
+/// 按每行一张发票写入JSON Lines文件
+pub fn generate_jsonl(invoices: &[InvoiceFile], output_path: &Path) -> Result<()> {
+    let mut writer = create(output_path)?;
+    for invoice in invoices {
+        let record = JsonRecord {
+            schema_version: SCHEMA_VERSION,
+            invoice,
+        };
+        serde_json::to_writer(&mut writer, &record)
+            .map_err(|e| InvoiceError::io(output_path, e.into()))?;
+        writer
+            .write_all(b"\n")
+            .map_err(|e| InvoiceError::io(output_path, e))?;
+    }
+    writer
+        .flush()
+        .map_err(|e| InvoiceError::io(output_path, e))
+}
+
+fn create(output_path: &Path) -> Result<BufWriter<File>> {
+    File::create(output_path)
+        .map(BufWriter::new)
+        .map_err(|e| InvoiceError::io(output_path, e))
+}
@@ -0,0 +1,212 @@
+//! 识别结果导出
+//!
+//! 每种输出格式实现 [`Exporter`]，识别完成后由 [`export_all`] 依次执行。
+//! 某个导出器失败只记录在它自己的 [`ExportReport`] 中，不影响识别结果和其他导出器。
+
+pub mod csv;
+pub mod json;
+pub mod xlsx;
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::error::{InvoiceError, Result};
+use crate::extractor::{InvoiceFile, InvoiceInfo};
+use crate::tax_id::TaxIdCheck;
+
+pub use self::csv::{generate_csv, CsvEncoding, CsvOptions};
+pub use json::{generate_json, generate_jsonl, SCHEMA_VERSION};
+pub use xlsx::{generate_excel, XlsxOptions};
+
+/// 表格导出的列名，Excel与CSV共用
+pub const HEADERS: [&str; 22] = [
+    "序号", "文件夹", "文件名", "发票类型", "发票号码", "发票代码", "校验码", "开票日期",
+    "购买方", "购买方税号", "购买方税号校验", "销售方", "销售方税号", "销售方税号校验", "项目内容",
+    "不含税金额", "税率", "税额", "价税合计", "金额校验", "二维码核对", "备注",
+];
+
+/// 税号校验列：未识别出税号时为空
+pub(crate) fn tax_check_text(check: Option<TaxIdCheck>) -> &'static str {
+    check.map_or("", TaxIdCheck::label)
+}
+
+/// 金额校验列：金额 + 税额与价税合计是否一致、大写金额是否相符，无法核对时为空
+pub(crate) fn amount_check_text(info: &InvoiceInfo) -> &'static str {
+    if info.amount_mismatch {
+        "不一致"
+    } else if info.amount_in_words_mismatch() {
+        "大写不符"
+    } else if info.amount_verified || info.amounts_consistent() == Some(true) {
+        "一致"
+    } else {
+        ""
+    }
+}
+
+/// 二维码核对列：列出与二维码不一致的字段，没有二维码或无法核对时为空
+pub(crate) fn qr_check_text(info: &InvoiceInfo) -> String {
+    if !info.qr_mismatch.is_empty() {
+        let details: Vec<String> = info.qr_mismatch.iter().map(|m| m.to_string()).collect();
+        format!("不一致: {}", details.join("；"))
+    } else if info.qr_verified {
+        "一致".to_string()
+    } else {
+        String::new()
+    }
+}
+
+/// 识别结果的输出目标
+pub trait Exporter: Send + Sync {
+    /// 格式名称，用于日志，如 "Excel"
+    fn name(&self) -> &str;
+
+    /// 输出文件路径
+    fn path(&self) -> &Path;
+
+    /// 写出全部发票
+    fn export(&self, invoices: &[InvoiceFile]) -> Result<()>;
+}
+
+/// 单个导出器的执行结果
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportReport {
+    pub format: String,
+    pub path: PathBuf,
+    /// 导出失败时的错误
+    pub error: Option<InvoiceError>,
+}
+
+impl ExportReport {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// 依次执行所有导出器，失败不会中断后续导出
+pub fn export_all(invoices: &[InvoiceFile], exporters: &[Arc<dyn Exporter>]) -> Vec<ExportReport> {
+    exporters
+        .iter()
+        .map(|exporter| run_exporter(invoices, exporter.as_ref()))
+        .collect()
+}
+
+/// 执行单个导出器并记录结果
+pub(crate) fn run_exporter(invoices: &[InvoiceFile], exporter: &dyn Exporter) -> ExportReport {
+    ExportReport {
+        format: exporter.name().to_string(),
+        path: exporter.path().to_path_buf(),
+        error: exporter.export(invoices).err(),
+    }
+}
+
+/// Excel导出器
+#[derive(Debug, Clone)]
+pub struct XlsxExporter {
+    pub path: PathBuf,
+    pub options: XlsxOptions,
+}
+
+impl XlsxExporter {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self::with_options(path, XlsxOptions::default())
+    }
+
+    pub fn with_options(path: impl Into<PathBuf>, options: XlsxOptions) -> Self {
+        Self {
+            path: path.into(),
+            options,
+        }
+    }
+}
+
+impl Exporter for XlsxExporter {
+    fn name(&self) -> &str {
+        "Excel"
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn export(&self, invoices: &[InvoiceFile]) -> Result<()> {
+        generate_excel(invoices, &self.path, &self.options)
+    }
+}
+
+/// CSV导出器
+#[derive(Debug, Clone)]
+pub struct CsvExporter {
+    pub path: PathBuf,
+    pub options: CsvOptions,
+}
+
+impl CsvExporter {
+    pub fn new(path: impl Into<PathBuf>, options: CsvOptions) -> Self {
+        Self {
+            path: path.into(),
+            options,
+        }
+    }
+}
+
+impl Exporter for CsvExporter {
+    fn name(&self) -> &str {
+        "CSV"
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn export(&self, invoices: &[InvoiceFile]) -> Result<()> {
+        generate_csv(invoices, &self.path, &self.options)
+    }
+}
+
+/// JSON导出器，`lines` 为真时输出JSON Lines
+#[derive(Debug, Clone)]
+pub struct JsonExporter {
+    pub path: PathBuf,
+    pub lines: bool,
+}
+
+impl JsonExporter {
+    /// 整批输出为一个JSON对象
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lines: false,
+        }
+    }
+
+    /// 每行一张发票
+    pub fn lines(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lines: true,
+        }
+    }
+}
+
+impl Exporter for JsonExporter {
+    fn name(&self) -> &str {
+        if self.lines {
+            "JSON Lines"
+        } else {
+            "JSON"
+        }
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn export(&self, invoices: &[InvoiceFile]) -> Result<()> {
+        if self.lines {
+            generate_jsonl(invoices, &self.path)
+        } else {
+            generate_json(invoices, &self.path)
+        }
+    }
+}
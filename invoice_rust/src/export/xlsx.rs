@@ -0,0 +1,218 @@
+//! Excel 导出
+//!
+//! 第一个工作表"发票清单"每张发票一行；启用明细表时第二个工作表"明细"
+//! 每个明细行一行，序号链接回发票清单中对应的行。
+
+use std::path::Path;
+
+use super::{amount_check_text, qr_check_text, tax_check_text, HEADERS};
+use crate::error::{InvoiceError, Result};
+use crate::extractor::InvoiceFile;
+use crate::tax_id::TaxIdCheck;
+
+/// 发票清单工作表名称
+const INVOICE_SHEET: &str = "发票清单";
+/// 明细工作表名称
+const ITEM_SHEET: &str = "明细";
+
+/// 明细表的列名
+pub const ITEM_HEADERS: [&str; 12] = [
+    "序号", "文件名", "发票号码", "行号", "项目名称", "规格型号", "单位",
+    "数量", "单价", "金额", "税率", "税额",
+];
+
+/// Excel导出选项
+#[derive(Debug, Clone, Default)]
+pub struct XlsxOptions {
+    /// 增加"明细"工作表
+    pub line_items: bool,
+}
+
+/// 生成Excel文件
+pub fn generate_excel(invoices: &[InvoiceFile], output_path: &Path, options: &XlsxOptions) -> Result<()> {
+    use rust_xlsxwriter::*;
+
+    let xlsx_err = |e| InvoiceError::excel(output_path, e);
+
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name(INVOICE_SHEET).map_err(xlsx_err)?;
+
+    // 设置列宽
+    worksheet.set_column_width(0, 6.0).map_err(xlsx_err)?;   // 序号
+    worksheet.set_column_width(1, 26.0).map_err(xlsx_err)?;  // 文件夹
+    worksheet.set_column_width(2, 36.0).map_err(xlsx_err)?;  // 文件名
+    worksheet.set_column_width(3, 22.0).map_err(xlsx_err)?;  // 发票类型
+    worksheet.set_column_width(4, 18.0).map_err(xlsx_err)?;  // 发票号码
+    worksheet.set_column_width(5, 13.0).map_err(xlsx_err)?;  // 发票代码
+    worksheet.set_column_width(6, 20.0).map_err(xlsx_err)?;  // 校验码
+    worksheet.set_column_width(7, 11.0).map_err(xlsx_err)?;  // 开票日期
+    worksheet.set_column_width(8, 22.0).map_err(xlsx_err)?;  // 购买方
+    worksheet.set_column_width(9, 16.0).map_err(xlsx_err)?;  // 购买方税号
+    worksheet.set_column_width(10, 12.0).map_err(xlsx_err)?; // 购买方税号校验
+    worksheet.set_column_width(11, 28.0).map_err(xlsx_err)?; // 销售方
+    worksheet.set_column_width(12, 16.0).map_err(xlsx_err)?; // 销售方税号
+    worksheet.set_column_width(13, 12.0).map_err(xlsx_err)?; // 销售方税号校验
+    worksheet.set_column_width(14, 18.0).map_err(xlsx_err)?; // 项目内容
+    worksheet.set_column_width(15, 11.0).map_err(xlsx_err)?; // 不含税金额
+    worksheet.set_column_width(16, 8.0).map_err(xlsx_err)?;  // 税率
+    worksheet.set_column_width(17, 10.0).map_err(xlsx_err)?; // 税额
+    worksheet.set_column_width(18, 10.0).map_err(xlsx_err)?; // 价税合计
+    worksheet.set_column_width(19, 9.0).map_err(xlsx_err)?;  // 金额校验
+    worksheet.set_column_width(20, 30.0).map_err(xlsx_err)?; // 二维码核对
+    worksheet.set_column_width(21, 12.0).map_err(xlsx_err)?; // 备注
+
+    // 表头样式
+    let header_format = Format::new()
+        .set_bold()
+        .set_font_size(10)
+        .set_font_color(Color::RGB(0xFFFFFF))
+        .set_background_color(Color::RGB(0x4472C4))
+        .set_align(FormatAlign::Center)
+        .set_align(FormatAlign::VerticalCenter)
+        .set_border(FormatBorder::Thin);
+
+    // 表头
+    for (col, header) in HEADERS.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, *header, &header_format)
+            .map_err(xlsx_err)?;
+    }
+
+    // 数据格式
+    let border_format = Format::new().set_border(FormatBorder::Thin);
+    let flagged_format = Format::new()
+        .set_border(FormatBorder::Thin)
+        .set_font_color(Color::RGB(0x9C0006))
+        .set_background_color(Color::RGB(0xFFC7CE));
+
+    // 写入数据
+    for (idx, inv) in invoices.iter().enumerate() {
+        let row = (idx + 1) as u32;
+        worksheet.write_number_with_format(row, 0, (idx + 1) as f64, &border_format)
+            .map_err(xlsx_err)?;
+        worksheet.write_string_with_format(row, 1, &inv.folder, &border_format)
+            .map_err(xlsx_err)?;
+        worksheet.write_string_with_format(row, 2, &inv.filename, &border_format)
+            .map_err(xlsx_err)?;
+        worksheet.write_string_with_format(row, 3, inv.info.invoice_type.label(), &border_format)
+            .map_err(xlsx_err)?;
+        worksheet.write_string_with_format(row, 4, &inv.info.invoice_number, &border_format)
+            .map_err(xlsx_err)?;
+        worksheet.write_string_with_format(row, 5, &inv.info.invoice_code, &border_format)
+            .map_err(xlsx_err)?;
+        worksheet.write_string_with_format(row, 6, &inv.info.check_code, &border_format)
+            .map_err(xlsx_err)?;
+        worksheet.write_string_with_format(row, 7, &inv.info.invoice_date, &border_format)
+            .map_err(xlsx_err)?;
+        worksheet.write_string_with_format(row, 8, &inv.info.buyer, &border_format)
+            .map_err(xlsx_err)?;
+        worksheet.write_string_with_format(row, 9, &inv.info.buyer_tax_number, &border_format)
+            .map_err(xlsx_err)?;
+        // 17位税号无法校验，不标红
+        let tax_format = |check| if check == Some(TaxIdCheck::Invalid) { &flagged_format } else { &border_format };
+        let buyer_tax_check = inv.info.buyer_tax_number_check;
+        worksheet.write_string_with_format(row, 10, tax_check_text(buyer_tax_check), tax_format(buyer_tax_check))
+            .map_err(xlsx_err)?;
+        worksheet.write_string_with_format(row, 11, &inv.info.seller, &border_format)
+            .map_err(xlsx_err)?;
+        worksheet.write_string_with_format(row, 12, &inv.info.seller_tax_number, &border_format)
+            .map_err(xlsx_err)?;
+        let seller_tax_check = inv.info.seller_tax_number_check;
+        worksheet.write_string_with_format(row, 13, tax_check_text(seller_tax_check), tax_format(seller_tax_check))
+            .map_err(xlsx_err)?;
+        worksheet.write_string_with_format(row, 14, &inv.info.item_content, &border_format)
+            .map_err(xlsx_err)?;
+        worksheet.write_string_with_format(row, 15, &inv.info.amount_without_tax, &border_format)
+            .map_err(xlsx_err)?;
+        worksheet.write_string_with_format(row, 16, &inv.info.tax_rate, &border_format)
+            .map_err(xlsx_err)?;
+        worksheet.write_string_with_format(row, 17, &inv.info.tax_amount, &border_format)
+            .map_err(xlsx_err)?;
+        worksheet.write_string_with_format(row, 18, &inv.info.amount, &border_format)
+            .map_err(xlsx_err)?;
+        let check_format = if inv.info.amount_mismatch || inv.info.amount_in_words_mismatch() {
+            &flagged_format
+        } else {
+            &border_format
+        };
+        worksheet.write_string_with_format(row, 19, amount_check_text(&inv.info), check_format)
+            .map_err(xlsx_err)?;
+        let qr_format = if inv.info.qr_mismatch.is_empty() { &border_format } else { &flagged_format };
+        worksheet.write_string_with_format(row, 20, qr_check_text(&inv.info), qr_format)
+            .map_err(xlsx_err)?;
+        worksheet.write_string_with_format(row, 21, inv.remark_text(), &border_format)
+            .map_err(xlsx_err)?;
+    }
+
+    // 冻结首行
+    worksheet.set_freeze_panes(1, 0)
+        .map_err(xlsx_err)?;
+
+    if options.line_items {
+        let worksheet = workbook.add_worksheet();
+        worksheet.set_name(ITEM_SHEET).map_err(xlsx_err)?;
+
+        worksheet.set_column_width(0, 6.0).map_err(xlsx_err)?;   // 序号
+        worksheet.set_column_width(1, 36.0).map_err(xlsx_err)?;  // 文件名
+        worksheet.set_column_width(2, 22.0).map_err(xlsx_err)?;  // 发票号码
+        worksheet.set_column_width(3, 6.0).map_err(xlsx_err)?;   // 行号
+        worksheet.set_column_width(4, 30.0).map_err(xlsx_err)?;  // 项目名称
+        worksheet.set_column_width(5, 16.0).map_err(xlsx_err)?;  // 规格型号
+        worksheet.set_column_width(6, 8.0).map_err(xlsx_err)?;   // 单位
+        worksheet.set_column_width(7, 10.0).map_err(xlsx_err)?;  // 数量
+        worksheet.set_column_width(8, 16.0).map_err(xlsx_err)?;  // 单价
+        worksheet.set_column_width(9, 12.0).map_err(xlsx_err)?;  // 金额
+        worksheet.set_column_width(10, 10.0).map_err(xlsx_err)?; // 税率
+        worksheet.set_column_width(11, 12.0).map_err(xlsx_err)?; // 税额
+
+        for (col, header) in ITEM_HEADERS.iter().enumerate() {
+            worksheet.write_string_with_format(0, col as u16, *header, &header_format)
+                .map_err(xlsx_err)?;
+        }
+
+        let discount_format = Format::new()
+            .set_border(FormatBorder::Thin)
+            .set_font_color(Color::RGB(0x9C0006));
+
+        let mut row = 0u32;
+        for (idx, inv) in invoices.iter().enumerate() {
+            // 发票清单中对应的行，第1行是表头
+            let link = Url::new(format!("internal:'{}'!A{}", INVOICE_SHEET, idx + 2));
+            for (line, item) in inv.info.items.iter().enumerate() {
+                row += 1;
+                let format = if item.is_discount() { &discount_format } else { &border_format };
+                worksheet.write_url_with_options(row, 0, link.clone(), (idx + 1).to_string(), "", Some(&border_format))
+                    .map_err(xlsx_err)?;
+                worksheet.write_string_with_format(row, 1, &inv.filename, &border_format)
+                    .map_err(xlsx_err)?;
+                worksheet.write_string_with_format(row, 2, &inv.info.invoice_number, &border_format)
+                    .map_err(xlsx_err)?;
+                worksheet.write_number_with_format(row, 3, (line + 1) as f64, &border_format)
+                    .map_err(xlsx_err)?;
+                worksheet.write_string_with_format(row, 4, &item.name, format)
+                    .map_err(xlsx_err)?;
+                worksheet.write_string_with_format(row, 5, &item.spec, format)
+                    .map_err(xlsx_err)?;
+                worksheet.write_string_with_format(row, 6, &item.unit, format)
+                    .map_err(xlsx_err)?;
+                worksheet.write_string_with_format(row, 7, &item.quantity, format)
+                    .map_err(xlsx_err)?;
+                worksheet.write_string_with_format(row, 8, &item.unit_price, format)
+                    .map_err(xlsx_err)?;
+                worksheet.write_string_with_format(row, 9, &item.amount, format)
+                    .map_err(xlsx_err)?;
+                worksheet.write_string_with_format(row, 10, &item.tax_rate, format)
+                    .map_err(xlsx_err)?;
+                worksheet.write_string_with_format(row, 11, &item.tax_amount, format)
+                    .map_err(xlsx_err)?;
+            }
+        }
+
+        worksheet.set_freeze_panes(1, 0)
+            .map_err(xlsx_err)?;
+    }
+
+    workbook.save(output_path).map_err(xlsx_err)?;
+
+    Ok(())
+}
@@ -1,144 +1,367 @@
-use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::path::Path;
-use walkdir::WalkDir;
+
+use crate::amount_words::{parse_amount_in_words, parse_cents};
+use crate::config::OcrConfig;
+use crate::error::{InvoiceError, Result};
+use crate::export::ExportReport;
+use crate::invoice_type::InvoiceType;
+use crate::layout::{column_tax_rates, fill_parties, has_text_layer, load_pdf, read_pdf, PageLayout};
+use crate::line_items::{parse_line_items, LineItem};
+use crate::parties::parse_parties;
+use crate::qr::{read_qr_image, read_qr_pdf, InvoiceQr, QrMismatch};
+use crate::rules::RuleSet;
+use crate::tax_id::{check_tax_id, TaxIdCheck};
 
 /// 发票信息结构
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct InvoiceInfo {
+    #[serde(default)]
+    pub invoice_type: InvoiceType,
+    /// 发票号码：全电发票为20位，旧版电子发票为8位
     pub invoice_number: String,
+    /// 发票代码（12位），只有旧版电子发票有
+    pub invoice_code: String,
+    /// 校验码（20位），只有旧版电子发票有
+    pub check_code: String,
+    /// 发票代码或号码取自没有标签的12位、8位数字，可能是电话号码、机器编号等，不参与二维码核对
+    #[serde(default)]
+    pub invoice_number_guessed: bool,
     pub invoice_date: String,
     pub buyer: String,
     pub buyer_tax_number: String,
+    /// 购买方税号的校验结果，没有税号时为 `None`
+    #[serde(default)]
+    pub buyer_tax_number_check: Option<TaxIdCheck>,
     pub seller: String,
     pub seller_tax_number: String,
+    /// 销售方税号的校验结果，没有税号时为 `None`
+    #[serde(default)]
+    pub seller_tax_number_check: Option<TaxIdCheck>,
+    /// 第一个项目的类别，如 `*餐饮服务*`
     pub item_content: String,
+    /// 全部明细行
+    #[serde(default)]
+    pub items: Vec<LineItem>,
+    /// 价税合计
     pub amount: String,
+    /// 金额（不含税）
+    #[serde(default)]
+    pub amount_without_tax: String,
+    /// 税额，免税发票为 0.00
+    #[serde(default)]
+    pub tax_amount: String,
+    /// 税率，多个税率用"、"分隔，如 "13%、9%"
+    #[serde(default)]
+    pub tax_rate: String,
+    /// 金额 + 税额与价税合计相差超过1分
+    #[serde(default)]
+    pub amount_mismatch: bool,
+    /// 价税合计的大写金额，如 "壹佰圆整"
+    #[serde(default)]
+    pub amount_in_words: String,
+    /// 大写金额与价税合计一致
+    #[serde(default)]
+    pub amount_verified: bool,
     pub remark: String,
+    /// 票面二维码中的信息，识别出时发票号码、日期、金额等以二维码为准
+    #[serde(default)]
+    pub qr: Option<InvoiceQr>,
+    /// 文本识别结果与二维码不一致的字段，可能是修改过票面文字的PDF
+    #[serde(default)]
+    pub qr_mismatch: Vec<QrMismatch>,
+    /// 二维码与文本中的发票号码、开票日期、金额核对一致
+    #[serde(default)]
+    pub qr_verified: bool,
+    /// 扫描件PDF：没有可用的文本层，字段来自文字识别或二维码
+    #[serde(default)]
+    pub scanned: bool,
+    /// 文字识别失败的原因，此时字段只取自二维码；没有二维码时整个文件记为处理失败
+    #[serde(default)]
+    pub ocr_error: String,
 }
 
-impl Default for InvoiceInfo {
-    fn default() -> Self {
-        Self {
-            invoice_number: String::new(),
-            invoice_date: String::new(),
-            buyer: String::new(),
-            buyer_tax_number: String::new(),
-            seller: String::new(),
-            seller_tax_number: String::new(),
-            item_content: String::new(),
-            amount: String::new(),
-            remark: String::new(),
-        }
-    }
+/// 从PDF发票中提取信息
+///
+/// PDF无法解析或已加密时返回错误，而不是空的发票信息。
+/// 没有可用文本层的扫描件标记为 [`scanned`](InvoiceInfo::scanned)：编译时启用了 `ocr` 功能时
+/// 识别页面图片中的文字，否则只取二维码中的信息；两者都没有结果时返回 [`InvoiceError::NoTextLayer`]，
+/// 文字识别失败且没有二维码时返回识别错误。
+pub fn extract_invoice_info(pdf_path: &Path, buyer_keyword: Option<&str>) -> Result<InvoiceInfo> {
+    extract_invoice_info_with_rules(pdf_path, buyer_keyword, RuleSet::shared(), &OcrConfig::default())
 }
 
-/// 从PDF发票中提取信息
-pub fn extract_invoice_info(
+/// 使用指定的识别规则（如按配置文件编译的规则）和文字识别设置从PDF发票中提取信息
+pub fn extract_invoice_info_with_rules(
     pdf_path: &Path,
     buyer_keyword: Option<&str>,
-) -> Result<InvoiceInfo, String> {
-    let buyer_keyword = buyer_keyword.unwrap_or("");
+    rules: &RuleSet,
+    ocr: &OcrConfig,
+) -> Result<InvoiceInfo> {
+    // 提取PDF文本和文字位置
+    let doc = load_pdf(pdf_path)?;
+    let (text, pages) = read_pdf(pdf_path, &doc)?;
+    let qr = read_qr_pdf(&doc);
+    let file_name = pdf_path.file_name().map(|name| name.to_string_lossy());
+
+    let mut info = if has_text_layer(&text) {
+        let mut info = parse_text_with_layout(
+            &text,
+            pages.first(),
+            file_name.as_deref(),
+            buyer_keyword,
+            rules,
+        );
+        // 页面上找不到区块标题、没有识别出的双方信息，再按标签的先后查找
+        fill_parties(&mut info, &pages);
+        info
+    } else {
+        let mut info = match scanned_text(pdf_path, &doc, ocr) {
+            Ok(Some(text)) if !text.trim().is_empty() => {
+                parse_invoice_text(&text, file_name.as_deref(), buyer_keyword, rules)
+            }
+            Ok(_) if qr.is_some() => InvoiceInfo::default(),
+            // 与发票图片相同，文字识别失败时仍返回二维码中的信息
+            Err(e) if qr.is_some() => InvoiceInfo {
+                ocr_error: e.to_string(),
+                ..InvoiceInfo::default()
+            },
+            Err(e) => return Err(e),
+            Ok(_) => {
+                return Err(InvoiceError::NoTextLayer {
+                    path: pdf_path.to_path_buf(),
+                })
+            }
+        };
+        info.scanned = true;
+        info
+    };
+    if let Some(qr) = qr {
+        qr.apply(&mut info);
+    }
+    Ok(info)
+}
+
+/// 识别扫描件每一页的图片中的文字，未启用 `ocr` 功能、配置中关闭或没有页面图片时返回 `None`
+#[cfg(feature = "ocr")]
+fn scanned_text(pdf_path: &Path, doc: &lopdf::Document, ocr: &OcrConfig) -> Result<Option<String>> {
+    if !ocr.scanned_pdfs {
+        return Ok(None);
+    }
+    let mut pages = Vec::new();
+    for id in crate::pdf_images::page_scans(doc) {
+        if let Some(image) = crate::pdf_images::to_gray(doc, id) {
+            pages.push(crate::ocr::recognize_image(&image, pdf_path, ocr)?);
+        }
+    }
+    Ok((!pages.is_empty()).then(|| pages.join("\n")))
+}
 
-    // 提取PDF文本
-    let text = match pdf_extract::extract_text(pdf_path) {
-        Ok(t) => t,
-        Err(e) => {
-            return Ok(InvoiceInfo {
-                remark: format!("解析错误: {}", e),
-                ..Default::default()
-            });
+#[cfg(not(feature = "ocr"))]
+fn scanned_text(_pdf_path: &Path, _doc: &lopdf::Document, _ocr: &OcrConfig) -> Result<Option<String>> {
+    Ok(None)
+}
+
+/// 从PNG、JPG发票图片中提取信息
+///
+/// 识别图片中的二维码；编译时启用了 `ocr` 功能时还会识别图片中的文字，与PDF文本一样提取各字段。
+/// 都没有识别出时返回空的发票信息。文字识别失败时，识别出二维码的记下失败原因
+/// （[`ocr_error`](InvoiceInfo::ocr_error)）并返回二维码中的信息，否则返回错误。
+pub fn extract_image_info(image_path: &Path, buyer_keyword: Option<&str>) -> Result<InvoiceInfo> {
+    extract_image_info_with_rules(image_path, buyer_keyword, RuleSet::shared(), &OcrConfig::default())
+}
+
+/// 使用指定的识别规则和文字识别设置从发票图片中提取信息
+pub fn extract_image_info_with_rules(
+    image_path: &Path,
+    buyer_keyword: Option<&str>,
+    rules: &RuleSet,
+    ocr: &OcrConfig,
+) -> Result<InvoiceInfo> {
+    let image = image::open(image_path).map_err(|e| match e {
+        image::ImageError::IoError(e) => InvoiceError::io(image_path, e),
+        other => InvoiceError::ImageUnreadable {
+            path: image_path.to_path_buf(),
+            message: other.to_string(),
+        },
+    })?;
+
+    let qr = read_qr_image(&image.to_luma8());
+    let mut info = match image_text(image_path, ocr) {
+        Ok(Some(text)) if !text.trim().is_empty() => {
+            let file_name = image_path.file_name().map(|name| name.to_string_lossy());
+            parse_invoice_text(&text, file_name.as_deref(), buyer_keyword, rules)
         }
+        Ok(_) => InvoiceInfo::default(),
+        // 二维码是主要来源，文字识别失败时仍返回二维码中的信息
+        Err(e) if qr.is_some() => InvoiceInfo {
+            ocr_error: e.to_string(),
+            ..InvoiceInfo::default()
+        },
+        Err(e) => return Err(e),
     };
+    if let Some(qr) = qr {
+        qr.apply(&mut info);
+    }
+    Ok(info)
+}
+
+/// 识别图片中的文字，未启用 `ocr` 功能时返回 `None`
+#[cfg(feature = "ocr")]
+fn image_text(image_path: &Path, ocr: &OcrConfig) -> Result<Option<String>> {
+    crate::ocr::recognize(image_path, ocr).map(Some)
+}
+
+#[cfg(not(feature = "ocr"))]
+fn image_text(_image_path: &Path, _ocr: &OcrConfig) -> Result<Option<String>> {
+    Ok(None)
+}
+
+/// 从发票文本中提取信息
+///
+/// `file_name` 用于金额的备用来源（如 "123.45.pdf"）。
+pub fn parse_invoice_text(
+    text: &str,
+    file_name: Option<&str>,
+    buyer_keyword: Option<&str>,
+    rules: &RuleSet,
+) -> InvoiceInfo {
+    parse_text_with_layout(text, None, file_name, buyer_keyword, rules)
+}
 
+/// 从发票文本中提取信息，`page` 为第一页的文字位置，用于区分左右并排的区块和表格的列
+pub(crate) fn parse_text_with_layout(
+    text: &str,
+    page: Option<&PageLayout>,
+    file_name: Option<&str>,
+    buyer_keyword: Option<&str>,
+    rules: &RuleSet,
+) -> InvoiceInfo {
+    let buyer_keyword = buyer_keyword.unwrap_or("");
     let mut info = InvoiceInfo::default();
 
-    // 提取发票号码 (20位纯数字)
-    let fp_regex = Regex::new(r"\b(\d{20})\b").unwrap();
-    if let Some(caps) = fp_regex.captures(&text) {
+    // 提取发票号码：全电发票为20位纯数字，
+    // 旧版电子发票为12位发票代码 + 8位发票号码
+    if let Some(caps) = rules.invoice_number.captures(text) {
         info.invoice_number = caps.get(1).unwrap().as_str().to_string();
+    } else {
+        // 优先取标签后的值，机器编号等其他12位数字可能排在发票代码前面；
+        // 没有标签时任意12位、8位数字都可能匹配，只在文本像是发票时才取
+        let code_label = rules.invoice_code_label.captures(text);
+        let number_label = rules.old_invoice_number_label.captures(text);
+        let guessed = code_label.is_none() || number_label.is_none();
+        let is_invoice = InvoiceType::classify(text, &info) != InvoiceType::NotInvoice;
+        let code = code_label.or_else(|| rules.invoice_code.captures(text).filter(|_| is_invoice));
+        let number = number_label.or_else(|| rules.old_invoice_number.captures(text).filter(|_| is_invoice));
+        if let (Some(code), Some(number)) = (code, number) {
+            info.invoice_code = code.get(1).unwrap().as_str().to_string();
+            info.invoice_number = number.get(1).unwrap().as_str().to_string();
+            info.invoice_number_guessed = guessed;
+        }
+
+        // 校验码被空格分成四组，合并为20位
+        if let Some(caps) = rules.check_code.captures(text) {
+            info.check_code = (1..=4).map(|i| caps.get(i).unwrap().as_str()).collect();
+        }
     }
 
     // 提取开票日期
-    let date_regex = Regex::new(r"(\d{4})年(\d{1,2})月(\d{1,2})日").unwrap();
-    if let Some(caps) = date_regex.captures(&text) {
+    if let Some(caps) = rules.date.captures(text) {
         let year = caps.get(1).unwrap().as_str();
         let month = format!("{:02}", caps.get(2).unwrap().as_str().parse::<u32>().unwrap_or(0));
         let day = format!("{:02}", caps.get(3).unwrap().as_str().parse::<u32>().unwrap_or(0));
         info.invoice_date = format!("{}-{}-{}", year, month, day);
     }
 
-    // 提取税号（18位，可能包含字母）
-    let tax_regex = Regex::new(r"\b[0-9A-Z]{18}\b").unwrap();
-    let tax_numbers: Vec<String> = tax_regex
-        .find_iter(&text)
-        .map(|m| m.as_str().to_string())
-        .collect();
-    
-    let valid_taxes: Vec<String> = tax_numbers
-        .into_iter()
-        .filter(|t| !(t.chars().all(|c| c.is_ascii_digit()) && t.len() == 20))
-        .collect();
+    // 按"购买方信息"/"销售方信息"区块中的标签识别双方，找不到标签时再按出现顺序猜测
+    let parties = parse_parties(text, rules, page);
 
-    if valid_taxes.len() >= 1 {
-        info.buyer_tax_number = valid_taxes[0].clone();
-    }
-    if valid_taxes.len() >= 2 {
-        info.seller_tax_number = valid_taxes[1].clone();
+    if let Some(parties) = parties.as_ref().filter(|p| p.has_tax_labels) {
+        if let Some(tax) = &parties.buyer.tax_number {
+            info.buyer_tax_number_check = Some(check_tax_id(tax));
+            info.buyer_tax_number = tax.clone();
+        }
+        if let Some(tax) = &parties.seller.tax_number {
+            info.seller_tax_number_check = Some(check_tax_id(tax));
+            info.seller_tax_number = tax.clone();
+        }
+    } else {
+        // 提取税号：18位统一社会信用代码，以及15、20位的旧版识别号
+        // 18位的无论校验是否通过都保留（标记为无效），旧版长度只保留校验通过的，
+        // 以免把银行账号、20位发票号码等误认为税号；17位的没有校验位，只在有税号标签时识别
+        let mut tax_numbers: Vec<(&str, TaxIdCheck)> = Vec::new();
+        for m in rules.tax_number.find_iter(text) {
+            let tax = m.as_str();
+            let check = check_tax_id(tax);
+            if tax == info.invoice_number
+                || (tax.len() != 18 && check != TaxIdCheck::Valid)
+                || tax_numbers.iter().any(|(t, _)| *t == tax)
+            {
+                continue;
+            }
+            tax_numbers.push((tax, check));
+        }
+
+        // 有两个以上校验通过的税号时忽略其余无效的
+        if tax_numbers.iter().filter(|(_, check)| *check == TaxIdCheck::Valid).count() >= 2 {
+            tax_numbers.retain(|(_, check)| *check == TaxIdCheck::Valid);
+        }
+
+        if let Some((tax, check)) = tax_numbers.first() {
+            info.buyer_tax_number = tax.to_string();
+            info.buyer_tax_number_check = Some(*check);
+        }
+        if let Some((tax, check)) = tax_numbers.get(1) {
+            info.seller_tax_number = tax.to_string();
+            info.seller_tax_number_check = Some(*check);
+        }
     }
 
-    // 提取项目内容
-    let item_regex = Regex::new(r"\*([^*]+)\*").unwrap();
-    if let Some(caps) = item_regex.captures(&text) {
-        let item = caps.get(0).unwrap().as_str();
+    // 提取项目内容：有明细表时取第一行名称中的类别（名称没有类别时取整个名称），
+    // 否则取文本中第一个 *类别*；密码区的乱码也可能被星号包围，有明细表时不从全文查找
+    info.items = parse_line_items(text, rules);
+    let item = match info.items.first() {
+        Some(first) => Some(rules.item.find(&first.name).map_or(first.name.as_str(), |m| m.as_str())),
+        None => rules.item.find(text).map(|m| m.as_str()),
+    };
+    if let Some(item) = item {
         info.item_content = item.chars().take(30).collect();
     }
 
-    // 排除模式
-    let exclude_patterns = vec![
-        r"\*[^*]+\*",
-        r"项目|规格|单位|数量|单价|金额|税率|税额|合计|备注|开票人|下载次数|发票号码|开票日期",
-        r"国家税务总局|发票监制|电子发票|普通发票|广东省税务局",
-        r"价税合计|大写|小写",
-    ];
-
-    // 销售方关键词
-    let seller_keywords = vec![
-        "有限公司", "股份有限公司", "科技", "网络", "文化", "婴童",
-        "贸易", "酒店", "饭店", "娱乐", "百货", "商店",
-        "餐饮店", "饮食店", "加油站", "石油化工",
-        "商行", "电子商务商行",
-    ];
-
     // 提取所有可能的销售方名称
     let mut all_sellers = Vec::new();
-    let lines: Vec<&str> = text.lines().collect();
     
-    for line in lines {
+    for line in text.lines() {
         let line = line.trim();
         if line.len() < 5 || line.len() > 60 {
             continue;
         }
         
         // 检查排除模式
-        let should_exclude = exclude_patterns.iter().any(|pattern| {
-            Regex::new(pattern).unwrap().is_match(line)
-        });
-        if should_exclude {
+        if rules.exclude.is_match(line) {
             continue;
         }
 
         // 检查是否包含销售方关键词
-        if seller_keywords.iter().any(|kw| line.contains(kw)) {
-            if !line.ends_with('费') {
-                if !all_sellers.contains(&line.to_string()) {
-                    all_sellers.push(line.to_string());
-                }
-            }
+        if rules.seller_keywords.iter().any(|kw| line.contains(kw.as_str()))
+            && !line.ends_with('费')
+            && !all_sellers.contains(&line.to_string())
+        {
+            all_sellers.push(line.to_string());
         }
     }
 
-    // 识别购买方（根据传入的关键词匹配）
-    if !buyer_keyword.is_empty() {
+    let labeled_buyer = parties.as_ref().and_then(|p| p.buyer.name.clone());
+    let labeled_seller = parties.as_ref().and_then(|p| p.seller.name.clone());
+    // 有"名称："标签时，标签为空的一方就是空的，不再用其他行填充
+    if parties.as_ref().is_some_and(|p| p.has_name_labels) {
+        all_sellers.clear();
+    }
+
+    // 识别购买方：优先取标签，其次根据传入的关键词匹配
+    if let Some(buyer) = labeled_buyer {
+        info.buyer = buyer;
+    } else if !buyer_keyword.is_empty() {
         for seller in &all_sellers {
             if seller.contains(buyer_keyword) {
                 info.buyer = seller.clone();
@@ -150,312 +373,408 @@ pub fn extract_invoice_info(
         info.buyer = all_sellers[0].clone();
     }
 
-    // 销售方是第二个不同的商家
-    for seller in &all_sellers {
-        if seller != &info.buyer {
-            info.seller = seller.clone();
-            break;
-        }
+    // 销售方：优先取标签，否则是第二个不同的商家
+    if let Some(seller) = labeled_seller {
+        info.seller = seller;
+    } else if let Some(seller) = all_sellers.iter().find(|seller| **seller != info.buyer) {
+        info.seller = seller.clone();
     }
 
     // 如果没找到，尝试从税号附近提取
     if info.seller.is_empty() && !info.seller_tax_number.is_empty() {
         let tax = &info.seller_tax_number;
         if let Some(idx) = text.find(tax) {
-            let start = idx.saturating_sub(100);
-            let end = (idx + 100).min(text.len());
+            // 前后各取约100字节，边界对齐到字符，避免切在中文字符中间
+            let mut start = idx.saturating_sub(100);
+            while !text.is_char_boundary(start) {
+                start -= 1;
+            }
+            let mut end = (idx + 100).min(text.len());
+            while !text.is_char_boundary(end) {
+                end += 1;
+            }
             let context = &text[start..end];
             
-            let keywords = vec!["店", "商行", "有限公司", "商贸", "科技", "贸易", "酒店", "饭店", "餐饮"];
-            for kw in keywords {
-                let escaped_kw = regex::escape(kw);
-                let pattern = format!(r"([^\s\n]+{}[^\s\n]*)", escaped_kw);
-                if let Ok(kw_regex) = Regex::new(&pattern) {
-                    if let Some(caps) = kw_regex.captures(context) {
-                        let seller = caps.get(1).unwrap().as_str()
-                            .trim_matches(|c: char| c == '*' || c == '、' || c == '。' || c == '.' || c == '\n' || c == '\t' || c == '\r');
-                        if seller != info.buyer && seller.len() > 4 {
-                            info.seller = seller.to_string();
-                            break;
-                        }
+            for kw_regex in &rules.context_keywords {
+                if let Some(caps) = kw_regex.captures(context) {
+                    let seller = caps.get(1).unwrap().as_str()
+                        .trim_matches(|c: char| c == '*' || c == '、' || c == '。' || c == '.' || c == '\n' || c == '\t' || c == '\r');
+                    if seller != info.buyer && seller.len() > 4 {
+                        info.seller = seller.to_string();
+                        break;
                     }
                 }
             }
         }
     }
 
-    // 提取金额 - 优先找"圆整"后的金额
-    let yuanzheng_regex = Regex::new(r"圆整\s*[¥￥]?\s*([\d,]+\.?\d*)").unwrap();
-    if let Some(caps) = yuanzheng_regex.captures(&text) {
-        info.amount = caps.get(1).unwrap().as_str().replace(',', "");
-    } else {
-        // 找所有¥后的金额，取最大的（价税合计通常是最大的）
-        let amount_regex = Regex::new(r"[¥￥]\s*([\d,]+\.?\d*)").unwrap();
-        let mut amounts_float: Vec<(f64, String)> = Vec::new();
-        
-        for caps in amount_regex.captures_iter(&text) {
-            if let Some(amt_str) = caps.get(1) {
-                let amt_str_clean = amt_str.as_str().replace(',', "");
-                if let Ok(amt) = amt_str_clean.parse::<f64>() {
-                    if amt > 0.0 && amt < 10000000.0 {
-                        amounts_float.push((amt, amt_str.as_str().to_string()));
-                    }
-                }
+    // 合计行：不含税金额和税额，免税发票的税额为 ***
+    if let Some(caps) = rules.subtotal.captures(text) {
+        info.amount_without_tax = caps[1].replace(',', "");
+        if let Some(tax) = caps.get(2) {
+            info.tax_amount = tax.as_str().replace(',', "");
+        } else if caps.get(3).is_some() {
+            info.tax_amount = "0.00".to_string();
+        }
+    }
+
+    // 税率，按出现顺序去重：优先取明细行的税率列，其次取页面坐标中税率列下的，
+    // 都没有时才在全文中查找（可能误取"酒精75%"等项目名称中的百分数）
+    let mut rates: Vec<String> = Vec::new();
+    for item in &info.items {
+        if rules.tax_rate.is_match(&item.tax_rate) && !rates.contains(&item.tax_rate) {
+            rates.push(item.tax_rate.clone());
+        }
+    }
+    if rates.is_empty() {
+        rates = page.map(|page| column_tax_rates(page, rules)).unwrap_or_default();
+    }
+    if rates.is_empty() {
+        for m in rules.tax_rate.find_iter(text) {
+            if !rates.iter().any(|rate| rate == m.as_str()) {
+                rates.push(m.as_str().to_string());
             }
         }
-        
-        if !amounts_float.is_empty() {
-            let max_amount = amounts_float.iter().max_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
-            if let Some(max) = max_amount {
-                info.amount = max.1.replace(',', "");
+    }
+    info.tax_rate = rates.join("、");
+
+    // 大写金额，用于核对和选择价税合计
+    let mut words_cents = None;
+    if let Some(m) = rules.amount_in_words.find(text) {
+        words_cents = parse_amount_in_words(m.as_str());
+        if words_cents.is_some() {
+            info.amount_in_words = m.as_str().to_string();
+        }
+    }
+
+    let lowercase = rules
+        .total_lowercase
+        .captures(text)
+        .map(|caps| caps[1].replace(',', ""));
+    let after_yuanzheng = rules
+        .amount_after_yuanzheng
+        .captures(text)
+        .map(|caps| caps[1].replace(',', ""));
+
+    // 所有¥后的金额
+    let mut amounts_float: Vec<(f64, String)> = Vec::new();
+    for caps in rules.amount.captures_iter(text) {
+        if let Some(amt_str) = caps.get(1) {
+            let amt_str_clean = amt_str.as_str().replace(',', "");
+            if let Ok(amt) = amt_str_clean.parse::<f64>() {
+                if amt > 0.0 && amt < rules.max_amount {
+                    amounts_float.push((amt, amt_str_clean));
+                }
             }
         }
     }
 
+    // 提取价税合计 - 优先取与大写金额相等的金额，
+    // 其次取"（小写）"后的金额、"圆整"后的金额，最后取最大的¥金额（价税合计通常是最大的）
+    let matches_words = words_cents.and_then(|cents| {
+        lowercase
+            .iter()
+            .chain(&after_yuanzheng)
+            .chain(amounts_float.iter().map(|(_, amt)| amt))
+            .find(|amt| parse_cents(amt) == Some(cents))
+            .cloned()
+    });
+    if let Some(amt) = matches_words {
+        info.amount = amt;
+    } else if let Some(amt) = lowercase {
+        info.amount = amt;
+    } else if let Some(amt) = after_yuanzheng {
+        info.amount = amt;
+    } else if let Some(max) = amounts_float.iter().max_by(|a, b| a.0.total_cmp(&b.0)) {
+        info.amount = max.1.clone();
+    }
+
     // 从文件名提取金额（备用方案）
     if info.amount.is_empty() {
-        if let Some(filename) = pdf_path.file_name() {
-            let filename_str = filename.to_string_lossy();
-            let filename_regex = Regex::new(r"(\d+\.?\d*)\.pdf").unwrap();
-            if let Some(caps) = filename_regex.captures(&filename_str) {
+        if let Some(filename_str) = file_name {
+            if let Some(caps) = rules.pdf_filename_amount.captures(filename_str) {
                 info.amount = caps.get(1).unwrap().as_str().to_string();
             }
         }
     }
 
-    Ok(info)
+    info.invoice_type = InvoiceType::classify(text, &info);
+    info.update_checks();
+
+    info
 }
 
 /// 发票文件信息
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct InvoiceFile {
     pub folder: String,
     pub filename: String,
     pub file_type: String,
     pub info: InvoiceInfo,
+    /// 处理失败时的错误，`info` 此时为空
+    pub error: Option<InvoiceError>,
 }
 
-impl Default for InvoiceFile {
-    fn default() -> Self {
-        Self {
-            folder: String::new(),
-            filename: String::new(),
-            file_type: String::new(),
-            info: InvoiceInfo::default(),
+impl InvoiceFile {
+    /// 是否为PDF发票
+    pub fn is_pdf(&self) -> bool {
+        self.file_type == "PDF"
+    }
+
+    /// 是否为可识别二维码的发票图片
+    pub fn is_image(&self) -> bool {
+        matches!(self.file_type.as_str(), "PNG" | "JPG" | "JPEG")
+    }
+
+    /// 是否处理失败
+    pub fn is_failed(&self) -> bool {
+        self.error.is_some()
+    }
+
+    /// 导出时的备注列：处理失败时显示错误信息，扫描件和文字识别失败的加上标记
+    pub fn remark_text(&self) -> String {
+        if let Some(e) = &self.error {
+            return format!("处理错误: {}", e);
+        }
+        let mut notes = Vec::new();
+        if self.info.scanned {
+            notes.push("扫描件");
         }
+        if !self.info.ocr_error.is_empty() {
+            notes.push(&self.info.ocr_error);
+        }
+        if !self.info.remark.is_empty() {
+            notes.push(&self.info.remark);
+        }
+        notes.join("；")
+    }
+}
+
+impl InvoiceInfo {
+    /// 必填字段
+    pub const REQUIRED_FIELDS: [&'static str; 4] = ["发票号码", "开票日期", "销售方", "价税合计"];
+
+    /// 金额 + 税额是否等于价税合计（允许1分的舍入误差），缺少任一项时返回 `None`
+    pub fn amounts_consistent(&self) -> Option<bool> {
+        let amount = parse_cents(&self.amount_without_tax)?;
+        let tax = parse_cents(&self.tax_amount)?;
+        let total = parse_cents(&self.amount)?;
+        Some(amount.checked_add(tax)?.checked_sub(total)?.abs() <= 1)
+    }
+
+    /// 按当前的金额重新核对：金额 + 税额与价税合计、大写金额与价税合计
+    pub(crate) fn update_checks(&mut self) {
+        self.amount_mismatch = self.amounts_consistent() == Some(false);
+        let words_cents = parse_amount_in_words(&self.amount_in_words);
+        self.amount_verified = words_cents.is_some() && parse_cents(&self.amount) == words_cents;
+    }
+
+    /// 识别出了大写金额，但与价税合计不一致
+    pub fn amount_in_words_mismatch(&self) -> bool {
+        !self.amount_in_words.is_empty() && !self.amount_verified
+    }
+
+    /// 返回未识别出的必填字段名称
+    pub fn missing_fields(&self) -> Vec<&'static str> {
+        let values = [
+            &self.invoice_number,
+            &self.invoice_date,
+            &self.seller,
+            &self.amount,
+        ];
+        Self::REQUIRED_FIELDS
+            .iter()
+            .zip(values)
+            .filter(|(_, value)| value.is_empty())
+            .map(|(name, _)| *name)
+            .collect()
     }
 }
 
 /// 处理结果
-#[derive(Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ProcessResult {
-    pub output_file: String,
     pub invoices: Vec<InvoiceFile>,
+    /// 各导出器的执行结果
+    pub exports: Vec<ExportReport>,
+    /// 处理中途被取消，`invoices` 只包含已处理的文件
+    pub cancelled: bool,
 }
 
-/// 处理所有发票文件并生成Excel
-pub fn process_invoices(
-    base_path: &Path,
-    buyer_keyword: Option<&str>,
-    output_path: Option<&Path>,
-) -> Result<ProcessResult, String> {
-    let mut all_invoices = Vec::new();
-
-    // 遍历目录
-    for entry in WalkDir::new(base_path)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-    {
-        let file_path = entry.path();
-        let file_name = entry.file_name().to_string_lossy();
-        
-        // 跳过隐藏文件
-        if file_name.starts_with('.') {
-            continue;
+impl ProcessResult {
+    /// 第一个成功写出的文件
+    pub fn output_file(&self) -> Option<&Path> {
+        self.exports
+            .iter()
+            .find(|report| report.is_ok())
+            .map(|report| report.path.as_path())
+    }
+
+    /// 统计识别情况
+    pub fn summary(&self) -> ProcessSummary {
+        let pdfs = || self.invoices.iter().filter(|inv| inv.is_pdf());
+        // PDF和图片都会识别，其他类型的文件只列出
+        let recognized = || self.invoices.iter().filter(|inv| inv.is_pdf() || inv.is_image());
+
+        ProcessSummary {
+            total_files: self.invoices.len(),
+            pdf_count: pdfs().count(),
+            with_seller: self
+                .invoices
+                .iter()
+                .filter(|inv| !inv.info.seller.is_empty() && !inv.info.seller.starts_with('*'))
+                .count(),
+            with_amount: self
+                .invoices
+                .iter()
+                .filter(|inv| !inv.info.amount.is_empty())
+                .count(),
+            amount_mismatch: pdfs().filter(|inv| inv.info.amount_mismatch).count(),
+            qr_mismatch: pdfs().filter(|inv| !inv.info.qr_mismatch.is_empty()).count(),
+            scanned: pdfs()
+                .filter(|inv| {
+                    inv.info.scanned
+                        || matches!(inv.error, Some(InvoiceError::NoTextLayer { .. }))
+                })
+                .count(),
+            failed: self.invoices.iter().filter(|inv| inv.is_failed()).count(),
+            incomplete: recognized()
+                .filter(|inv| !inv.is_failed() && !inv.info.missing_fields().is_empty())
+                .count(),
         }
+    }
+}
 
-        let file_ext = file_path
-            .extension()
-            .and_then(|s| s.to_str())
-            .unwrap_or("")
-            .to_uppercase();
+/// 识别统计
+#[derive(Debug, Clone, Default)]
+pub struct ProcessSummary {
+    pub total_files: usize,
+    pub pdf_count: usize,
+    pub with_seller: usize,
+    pub with_amount: usize,
+    /// 金额 + 税额与价税合计不一致的PDF数
+    pub amount_mismatch: usize,
+    /// 文本与二维码不一致的PDF数
+    pub qr_mismatch: usize,
+    /// 没有可用文本层的扫描件PDF数，含识别失败的
+    pub scanned: usize,
+    /// 处理失败的文件数（PDF和图片）
+    pub failed: usize,
+    /// 缺少必填字段的文件数（PDF和图片）
+    pub incomplete: usize,
+}
 
-        if !matches!(file_ext.as_str(), "PDF" | "PNG" | "JPG" | "JPEG") {
-            continue;
+impl ProcessSummary {
+    /// 销售方识别率（按PDF数计算）
+    pub fn seller_rate(&self) -> f64 {
+        if self.pdf_count > 0 {
+            (self.with_seller as f64 / self.pdf_count as f64) * 100.0
+        } else {
+            0.0
         }
+    }
 
-        let rel_path = entry
-            .path()
-            .parent()
-            .and_then(|p| p.strip_prefix(base_path).ok())
-            .and_then(|p| p.to_str())
-            .unwrap_or("")
-            .to_string();
-
-        let mut invoice_file = InvoiceFile {
-            folder: rel_path,
-            filename: file_name.to_string(),
-            file_type: file_ext.clone(),
-            info: InvoiceInfo::default(),
-        };
+    /// 金额识别率（按总文件数计算）
+    pub fn amount_rate(&self) -> f64 {
+        if self.total_files > 0 {
+            (self.with_amount as f64 / self.total_files as f64) * 100.0
+        } else {
+            0.0
+        }
+    }
+}
 
-        // 处理PDF文件
-        if file_ext == "PDF" {
-            match extract_invoice_info(file_path, buyer_keyword) {
-                Ok(info) => invoice_file.info = info,
-                Err(e) => {
-                    invoice_file.info.remark = format!("处理错误: {}", e);
-                }
-            }
+impl std::fmt::Display for ProcessSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "发票识别完成！")?;
+        writeln!(f, "  总文件数: {}", self.total_files)?;
+        writeln!(f, "  PDF发票数: {}", self.pdf_count)?;
+        writeln!(f, "  销售方识别率: {:.1}%", self.seller_rate())?;
+        write!(f, "  金额识别率: {:.1}%", self.amount_rate())?;
+        if self.amount_mismatch > 0 {
+            write!(f, "\n  金额校验不一致: {}", self.amount_mismatch)?;
         }
+        if self.qr_mismatch > 0 {
+            write!(f, "\n  二维码与文本不一致: {}", self.qr_mismatch)?;
+        }
+        if self.scanned > 0 {
+            write!(f, "\n  扫描件: {}", self.scanned)?;
+        }
+        Ok(())
+    }
+}
 
-        all_invoices.push(invoice_file);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corpus(name: &str) -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("benches/corpus").join(name);
+        std::fs::read_to_string(&path).unwrap()
     }
 
-    // 从图片文件名提取金额
-    for inv in &mut all_invoices {
-        if inv.info.amount.is_empty() && !inv.filename.to_uppercase().ends_with(".PDF") {
-            let filename_regex = Regex::new(r"(\d+\.?\d*)\.(?:PNG|JPG|JPEG)").unwrap();
-            if let Some(caps) = filename_regex.captures(&inv.filename.to_uppercase()) {
-                inv.info.amount = caps.get(1).unwrap().as_str().to_string();
-            }
+    #[test]
+    fn item_content_comes_from_first_line_item() {
+        for name in ["全电专票_餐饮.txt", "全电普票_办公用品.txt", "全电普票_加油.txt", "增值税电子普通发票_通行费.txt"] {
+            let info = parse_invoice_text(&corpus(name), None, None, RuleSet::shared());
+            let first = info.items.first().unwrap_or_else(|| panic!("{}: 没有明细行", name));
+            assert!(first.name.starts_with(&info.item_content), "{}: {} / {}", name, info.item_content, first.name);
         }
+        // 密码区中被星号包围的乱码排在明细表之前
+        let info = parse_invoice_text(&corpus("增值税电子普通发票_通行费.txt"), None, None, RuleSet::shared());
+        assert_eq!(info.item_content, "*经营租赁*");
     }
 
-    // 排序
-    all_invoices.sort_by(|a, b| {
-        a.folder
-            .cmp(&b.folder)
-            .then_with(|| a.filename.cmp(&b.filename))
-    });
-
-    // 生成Excel
-    let output_file = output_path
-        .map(|p| p.to_path_buf())
-        .unwrap_or_else(|| base_path.join("发票清单.xlsx"));
-
-    generate_excel(&all_invoices, &output_file)?;
-
-    // 统计识别率
-    let pdf_count = all_invoices
-        .iter()
-        .filter(|inv| inv.filename.to_uppercase().ends_with(".PDF"))
-        .count();
-    let with_seller = all_invoices
-        .iter()
-        .filter(|inv| {
-            !inv.info.seller.is_empty() && !inv.info.seller.starts_with('*')
-        })
-        .count();
-    let with_amount = all_invoices
-        .iter()
-        .filter(|inv| !inv.info.amount.is_empty())
-        .count();
-
-    let seller_rate = if pdf_count > 0 {
-        (with_seller as f64 / pdf_count as f64) * 100.0
-    } else {
-        0.0
-    };
-    let amount_rate = if !all_invoices.is_empty() {
-        (with_amount as f64 / all_invoices.len() as f64) * 100.0
-    } else {
-        0.0
-    };
+    #[test]
+    fn tax_rate_from_line_items() {
+        // 项目名称中的"75%"不是税率
+        let text = "电子发票（普通发票）\n发票号码：24312000000098765432\n\
+                    项目名称 规格型号 单 位 数 量 单 价 金 额 税率/征收率 税 额\n\
+                    *卫生用品*消毒酒精75% 500ml 瓶 2 8.85 17.70 13% 2.30\n\
+                    *文具*中性笔 0.5mm 盒 1 8.85 8.85 13% 1.15\n\
+                    合 计 ¥26.55 ¥3.45\n";
+        let info = parse_invoice_text(text, None, None, RuleSet::shared());
+        assert_eq!(info.items.len(), 2);
+        assert_eq!(info.tax_rate, "13%");
+
+        // 没有明细表时在全文中查找
+        let info = parse_invoice_text("电子发票（普通发票）\n税率 6%\n", None, None, RuleSet::shared());
+        assert_eq!(info.tax_rate, "6%");
+    }
 
-    println!("发票识别完成！");
-    println!("  总文件数: {}", all_invoices.len());
-    println!("  PDF发票数: {}", pdf_count);
-    println!("  销售方识别率: {:.1}%", seller_rate);
-    println!("  金额识别率: {:.1}%", amount_rate);
-    println!("\nExcel已保存: {}", output_file.display());
-
-    Ok(ProcessResult {
-        output_file: output_file.to_string_lossy().to_string(),
-        invoices: all_invoices,
-    })
-}
+    #[test]
+    fn old_invoice_number_prefers_labels() {
+        // 机器编号和电话号码排在发票代码、号码之前
+        let text = "增值税电子普通发票\n机器编号：499099123456\n电话：020-12345678\n\
+                    发票代码：044031900111\n发票号码：87654321\n";
+        let info = parse_invoice_text(text, None, None, RuleSet::shared());
+        assert_eq!(info.invoice_code, "044031900111");
+        assert_eq!(info.invoice_number, "87654321");
+        assert!(!info.invoice_number_guessed);
+    }
 
-/// 生成Excel文件
-fn generate_excel(invoices: &[InvoiceFile], output_path: &Path) -> Result<(), String> {
-    use rust_xlsxwriter::*;
-
-    let mut workbook = Workbook::new();
-    let worksheet = workbook.add_worksheet();
-
-    // 设置列宽
-    worksheet.set_column_width(0, 6.0).map_err(|e| format!("设置列宽失败: {}", e))?;   // 序号
-    worksheet.set_column_width(1, 26.0).map_err(|e| format!("设置列宽失败: {}", e))?;  // 文件夹
-    worksheet.set_column_width(2, 36.0).map_err(|e| format!("设置列宽失败: {}", e))?;  // 文件名
-    worksheet.set_column_width(3, 18.0).map_err(|e| format!("设置列宽失败: {}", e))?;  // 发票号码
-    worksheet.set_column_width(4, 11.0).map_err(|e| format!("设置列宽失败: {}", e))?;  // 开票日期
-    worksheet.set_column_width(5, 22.0).map_err(|e| format!("设置列宽失败: {}", e))?;  // 购买方
-    worksheet.set_column_width(6, 16.0).map_err(|e| format!("设置列宽失败: {}", e))?;  // 购买方税号
-    worksheet.set_column_width(7, 28.0).map_err(|e| format!("设置列宽失败: {}", e))?;  // 销售方
-    worksheet.set_column_width(8, 16.0).map_err(|e| format!("设置列宽失败: {}", e))?;  // 销售方税号
-    worksheet.set_column_width(9, 18.0).map_err(|e| format!("设置列宽失败: {}", e))?;  // 项目内容
-    worksheet.set_column_width(10, 10.0).map_err(|e| format!("设置列宽失败: {}", e))?; // 金额
-    worksheet.set_column_width(11, 12.0).map_err(|e| format!("设置列宽失败: {}", e))?; // 备注
-
-    // 表头样式
-    let header_format = Format::new()
-        .set_bold()
-        .set_font_size(10)
-        .set_font_color(Color::RGB(0xFFFFFF))
-        .set_background_color(Color::RGB(0x4472C4))
-        .set_align(FormatAlign::Center)
-        .set_align(FormatAlign::VerticalCenter)
-        .set_border(FormatBorder::Thin);
-
-    // 表头
-    let headers = vec![
-        "序号", "文件夹", "文件名", "发票号码", "开票日期", "购买方",
-        "购买方税号", "销售方", "销售方税号", "项目内容", "金额", "备注",
-    ];
-
-    for (col, header) in headers.iter().enumerate() {
-        worksheet.write_string_with_format(0, col as u16, *header, &header_format)
-            .map_err(|e| format!("写入表头失败: {}", e))?;
-    }
-
-    // 数据格式
-    let border_format = Format::new().set_border(FormatBorder::Thin);
-
-    // 写入数据
-    for (idx, inv) in invoices.iter().enumerate() {
-        let row = (idx + 1) as u32;
-        worksheet.write_number_with_format(row, 0, (idx + 1) as f64, &border_format)
-            .map_err(|e| format!("写入数据失败: {}", e))?;
-        worksheet.write_string_with_format(row, 1, &inv.folder, &border_format)
-            .map_err(|e| format!("写入数据失败: {}", e))?;
-        worksheet.write_string_with_format(row, 2, &inv.filename, &border_format)
-            .map_err(|e| format!("写入数据失败: {}", e))?;
-        worksheet.write_string_with_format(row, 3, &inv.info.invoice_number, &border_format)
-            .map_err(|e| format!("写入数据失败: {}", e))?;
-        worksheet.write_string_with_format(row, 4, &inv.info.invoice_date, &border_format)
-            .map_err(|e| format!("写入数据失败: {}", e))?;
-        worksheet.write_string_with_format(row, 5, &inv.info.buyer, &border_format)
-            .map_err(|e| format!("写入数据失败: {}", e))?;
-        worksheet.write_string_with_format(row, 6, &inv.info.buyer_tax_number, &border_format)
-            .map_err(|e| format!("写入数据失败: {}", e))?;
-        worksheet.write_string_with_format(row, 7, &inv.info.seller, &border_format)
-            .map_err(|e| format!("写入数据失败: {}", e))?;
-        worksheet.write_string_with_format(row, 8, &inv.info.seller_tax_number, &border_format)
-            .map_err(|e| format!("写入数据失败: {}", e))?;
-        worksheet.write_string_with_format(row, 9, &inv.info.item_content, &border_format)
-            .map_err(|e| format!("写入数据失败: {}", e))?;
-        worksheet.write_string_with_format(row, 10, &inv.info.amount, &border_format)
-            .map_err(|e| format!("写入数据失败: {}", e))?;
-        worksheet.write_string_with_format(row, 11, &inv.info.remark, &border_format)
-            .map_err(|e| format!("写入数据失败: {}", e))?;
-    }
-
-    // 冻结首行
-    worksheet.set_freeze_panes(1, 0)
-        .map_err(|e| format!("冻结首行失败: {}", e))?;
-
-    workbook.save(output_path).map_err(|e| format!("保存Excel失败: {}", e))?;
-
-    Ok(())
+    #[test]
+    fn unlabeled_numbers_only_in_invoices() {
+        // 不是发票的文本中的电话号码和机器编号不能当作发票代码和号码
+        let text = "收据\n机器编号：499099123456\n联系电话：020-12345678\n";
+        let info = parse_invoice_text(text, None, None, RuleSet::shared());
+        assert_eq!(info.invoice_code, "");
+        assert_eq!(info.invoice_number, "");
+        assert_eq!(info.invoice_type, InvoiceType::NotInvoice);
+
+        // 发票中没有标签时按位置猜测，但不参与二维码核对
+        let text = "增值税电子普通发票\n机器编号：499099123456\n联系电话：020-12345678\n";
+        let mut info = parse_invoice_text(text, None, None, RuleSet::shared());
+        assert_eq!(info.invoice_code, "499099123456");
+        assert_eq!(info.invoice_number, "12345678");
+        assert!(info.invoice_number_guessed);
+
+        let qr = InvoiceQr::parse("01,10,044031900111,12345678,100.50,20200101,12345678901234567890,ABCD,").unwrap();
+        assert_eq!(qr.compare(&info), (Vec::new(), false));
+        qr.apply(&mut info);
+        assert!(!info.qr_verified);
+        assert!(info.qr_mismatch.is_empty());
+        assert_eq!(info.invoice_code, "044031900111");
+        assert!(!info.invoice_number_guessed);
+    }
 }
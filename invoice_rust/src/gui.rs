@@ -2,9 +2,15 @@ use eframe::egui;
 use std::path::PathBuf;
 use std::sync::mpsc;
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use crate::extractor;
+use invoice_extractor::{
+    extractor, CancelToken, Config, InvoiceError, ProcessEvent, ProcessOptions, XlsxExporter,
+    XlsxOptions,
+};
+
+/// 配置文件中没有设置 `timeout` 时，单个文件的识别时间限制
+const DEFAULT_FILE_TIMEOUT: Duration = Duration::from_secs(60);
 
 // 专业配色系统
 const BG_PRIMARY: egui::Color32 = egui::Color32::from_rgb(18, 18, 18);
@@ -13,6 +19,7 @@ const BG_INPUT: egui::Color32 = egui::Color32::from_rgb(40, 40, 40);
 const BG_LOG: egui::Color32 = egui::Color32::from_rgb(12, 12, 12);
 const ACCENT_SUCCESS: egui::Color32 = egui::Color32::from_rgb(0, 200, 83);
 const ACCENT_TECH: egui::Color32 = egui::Color32::from_rgb(41, 121, 255);
+const ACCENT_DANGER: egui::Color32 = egui::Color32::from_rgb(229, 57, 53);
 const TEXT_HIGH: egui::Color32 = egui::Color32::from_rgb(255, 255, 255);
 const TEXT_MEDIUM: egui::Color32 = egui::Color32::from_rgb(158, 158, 158);
 const TEXT_LOW: egui::Color32 = egui::Color32::from_rgb(97, 97, 97);
@@ -43,13 +50,19 @@ impl ProcessStats {
 }
 
 pub struct InvoiceApp {
+    config: Config,
     invoice_dir: String,
     buyer_keyword: String,
     output_path: String,
+    export_line_items: bool,
     log_messages: Vec<String>,
     is_processing: bool,
     status_message: String,
-    result_receiver: Option<mpsc::Receiver<Result<extractor::ProcessResult, String>>>,
+    result_receiver: Option<mpsc::Receiver<Result<extractor::ProcessResult, InvoiceError>>>,
+    event_receiver: Option<mpsc::Receiver<ProcessEvent>>,
+    progress_done: usize,
+    progress_total: usize,
+    cancel_token: Option<CancelToken>,
     browse_dir_clicked: bool,
     browse_output_clicked: bool,
     open_result_clicked: bool,
@@ -64,13 +77,19 @@ pub struct InvoiceApp {
 impl Default for InvoiceApp {
     fn default() -> Self {
         Self {
+            config: Config::default(),
             invoice_dir: String::new(),
             buyer_keyword: String::new(),
             output_path: String::new(),
+            export_line_items: false,
             log_messages: Vec::new(),
             is_processing: false,
             status_message: "就绪".to_string(),
             result_receiver: None,
+            event_receiver: None,
+            progress_done: 0,
+            progress_total: 0,
+            cancel_token: None,
             browse_dir_clicked: false,
             browse_output_clicked: false,
             open_result_clicked: false,
@@ -86,7 +105,15 @@ impl Default for InvoiceApp {
 
 impl InvoiceApp {
     pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        Self::default()
+        let mut app = Self::default();
+        match Config::load() {
+            Ok(config) => {
+                app.buyer_keyword = config.defaults.buyer_keyword.clone();
+                app.config = config;
+            }
+            Err(e) => app.log(format!("⚠ {}，使用内置规则", e)),
+        }
+        app
     }
 
     fn log(&mut self, message: String) {
@@ -112,10 +139,15 @@ impl InvoiceApp {
         self.log_messages.clear();
         self.stats = ProcessStats::default();
         self.start_time = Some(Instant::now());
+        self.progress_done = 0;
+        self.progress_total = 0;
 
         let invoice_dir = self.invoice_dir.clone();
         let buyer_keyword = self.buyer_keyword.clone();
         let output_path = self.output_path.clone();
+        let xlsx_options = XlsxOptions {
+            line_items: self.export_line_items,
+        };
 
         self.log("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".to_string());
         self.log("⚡ 开始处理发票文件".to_string());
@@ -128,38 +160,122 @@ impl InvoiceApp {
         self.log("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".to_string());
 
         let (tx, rx) = mpsc::channel();
+        let (event_tx, event_rx) = mpsc::channel();
+        let cancel_token = CancelToken::new();
+
+        let mut builder = ProcessOptions::builder().config(&self.config);
+        if self.config.defaults.timeout.is_none() {
+            builder = builder.timeout(DEFAULT_FILE_TIMEOUT);
+        }
+        let options = builder
+            .buyer_keyword(buyer_keyword)
+            .exporter(XlsxExporter::with_options(&output_path, xlsx_options))
+            .on_event(move |event| {
+                let _ = event_tx.send(event.clone());
+            })
+            .cancel_token(cancel_token.clone())
+            .build();
 
         thread::spawn(move || {
             let base_path = PathBuf::from(&invoice_dir);
-            let output_path_buf = PathBuf::from(&output_path);
-            
-            let buyer_kw = if buyer_keyword.is_empty() {
-                None
-            } else {
-                Some(buyer_keyword.as_str())
-            };
-
-            let result = extractor::process_invoices(
-                &base_path,
-                buyer_kw,
-                Some(&output_path_buf),
-            );
+            let result = options.and_then(|options| invoice_extractor::process_invoices(&base_path, &options));
 
             let _ = tx.send(result);
         });
 
         self.result_receiver = Some(rx);
+        self.event_receiver = Some(event_rx);
+        self.cancel_token = Some(cancel_token);
+    }
+
+    /// 停止处理：不再开始新的文件，已处理的结果照常导出
+    fn stop_processing(&mut self) {
+        if let Some(token) = &self.cancel_token {
+            if !token.is_cancelled() {
+                token.cancel();
+                self.log("⏹ 正在停止，等待当前文件处理完成...".to_string());
+                self.status_message = "正在停止".to_string();
+            }
+        }
+    }
+
+    /// 处理工作线程发来的进度事件
+    fn poll_events(&mut self) {
+        let events: Vec<ProcessEvent> = match &self.event_receiver {
+            Some(rx) => rx.try_iter().collect(),
+            None => return,
+        };
+
+        for event in events {
+            match event {
+                ProcessEvent::Scanned { total } => {
+                    self.progress_total = total;
+                    self.log(format!("🔍 共找到 {} 个文件", total));
+                }
+                ProcessEvent::FileFinished { completed, invoice, .. } => {
+                    self.progress_done = completed;
+                    let amount = if invoice.info.amount.is_empty() {
+                        "-".to_string()
+                    } else {
+                        format!("¥{}", invoice.info.amount)
+                    };
+                    self.log(format!("  ✓ {}  {}", Self::invoice_path(&invoice), amount));
+                }
+                ProcessEvent::FileFailed { completed, invoice, .. } => {
+                    self.progress_done = completed;
+                    self.log(format!("❌ {}: {}", Self::invoice_path(&invoice), invoice.remark_text()));
+                }
+                ProcessEvent::Cancelled { completed, total } => {
+                    self.log(format!("⏹ 已停止，处理了 {}/{} 个文件", completed, total));
+                }
+                ProcessEvent::FileStarted { .. } | ProcessEvent::ExportFinished { .. } => {}
+            }
+        }
+    }
+
+    fn invoice_path(invoice: &extractor::InvoiceFile) -> String {
+        if invoice.folder.is_empty() {
+            invoice.filename.clone()
+        } else {
+            format!("{}/{}", invoice.folder, invoice.filename)
+        }
+    }
+
+    /// 进度条文字：已处理数和预计剩余时间
+    fn progress_text(&self) -> String {
+        let elapsed = self.start_time.map(|t| t.elapsed().as_secs_f64()).unwrap_or(0.0);
+        let remaining = self.progress_total.saturating_sub(self.progress_done);
+        if self.progress_done == 0 {
+            format!("{}/{}", self.progress_done, self.progress_total)
+        } else {
+            let eta = elapsed / self.progress_done as f64 * remaining as f64;
+            format!("{}/{} · 剩余约 {:.0} 秒", self.progress_done, self.progress_total, eta)
+        }
     }
 
     fn check_result(&mut self) {
         if let Some(ref rx) = self.result_receiver {
-            if let Ok(result) = rx.try_recv() {
+            let received = match rx.try_recv() {
+                Ok(result) => Some(result),
+                // 工作线程panic退出时发送端被丢弃，不能一直停在处理中
+                Err(mpsc::TryRecvError::Disconnected) => Some(Err(InvoiceError::Panicked {
+                    path: PathBuf::from(&self.invoice_dir),
+                    message: "处理线程异常退出".to_string(),
+                })),
+                Err(mpsc::TryRecvError::Empty) => None,
+            };
+            if let Some(result) = received {
+                // 结果之前发出的事件可能还没处理完
+                self.poll_events();
                 self.is_processing = false;
                 let elapsed = self.start_time.map(|t| t.elapsed().as_secs_f64()).unwrap_or(0.0);
                 
                 match result {
                     Ok(process_result) => {
-                        self.result_file_path = process_result.output_file.clone();
+                        self.result_file_path = process_result
+                            .output_file()
+                            .map(|p| p.to_string_lossy().to_string())
+                            .unwrap_or_default();
                         self.result_data = process_result.invoices.clone();
                         self.stats.elapsed_time = elapsed;
                         
@@ -178,16 +294,38 @@ impl InvoiceApp {
                         self.show_table = true;
                         
                         self.log("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".to_string());
-                        self.log("✅ 处理完成!".to_string());
+                        if process_result.cancelled {
+                            self.log("⏹ 已停止，以下为部分结果".to_string());
+                        } else {
+                            self.log("✅ 处理完成!".to_string());
+                        }
                         self.log(format!("📊 总文件: {} | PDF: {} | 成功率: {:.1}% | 耗时: {:.2}s", 
                             self.stats.total_files, 
                             self.stats.pdf_files,
                             self.stats.accuracy_rate(),
                             elapsed));
-                        self.log(format!("💾 输出: {}", Self::format_path(&process_result.output_file)));
+                        for report in &process_result.exports {
+                            match &report.error {
+                                None => self.log(format!("💾 输出: {}", Self::format_path(&report.path.to_string_lossy()))),
+                                Some(e) => self.log(format!("❌ 导出{}失败: {}", report.format, e)),
+                            }
+                        }
+                        for inv in &process_result.invoices {
+                            if !inv.info.qr_mismatch.is_empty() {
+                                let details: Vec<String> = inv.info.qr_mismatch.iter().map(|m| m.to_string()).collect();
+                                self.log(format!("❌ 二维码与文本不一致: {} ({})", inv.filename, details.join("；")));
+                            }
+                            if inv.info.scanned {
+                                self.log(format!("📷 扫描件: {}", inv.filename));
+                            }
+                        }
                         self.log("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".to_string());
                         
-                        self.status_message = "识别完成".to_string();
+                        self.status_message = if process_result.cancelled {
+                            "已停止（部分结果）".to_string()
+                        } else {
+                            "识别完成".to_string()
+                        };
                     }
                     Err(e) => {
                         self.log("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━".to_string());
@@ -197,6 +335,8 @@ impl InvoiceApp {
                     }
                 }
                 self.result_receiver = None;
+                self.event_receiver = None;
+                self.cancel_token = None;
             }
         }
     }
@@ -211,6 +351,16 @@ impl InvoiceApp {
         }
     }
     
+    /// 添加表格单元格，`highlight` 时标红背景
+    fn table_cell(row: &mut egui_extras::TableRow<'_, '_>, highlight: bool, add_contents: impl FnOnce(&mut egui::Ui)) {
+        row.col(|ui| {
+            if highlight {
+                ui.painter().rect_filled(ui.max_rect(), 0.0, ACCENT_DANGER.gamma_multiply(0.25));
+            }
+            add_contents(ui);
+        });
+    }
+
     fn render_data_card(&self, ui: &mut egui::Ui, label: &str, value: String, unit: &str, color: egui::Color32) {
         egui::Frame::none()
             .fill(BG_CARD)
@@ -282,6 +432,7 @@ impl eframe::App for InvoiceApp {
         }
 
         if self.is_processing {
+            self.poll_events();
             self.check_result();
             ctx.request_repaint();
         }
@@ -388,6 +539,11 @@ impl eframe::App for InvoiceApp {
                                                 self.browse_output_clicked = true;
                                             }
                                         });
+                                        ui.add_space(6.0);
+                                        ui.checkbox(
+                                            &mut self.export_line_items,
+                                            egui::RichText::new("导出明细表（每个项目一行）").size(12.0).color(TEXT_MEDIUM)
+                                        );
                                         
                                         ui.add_space(20.0);
 
@@ -405,6 +561,35 @@ impl eframe::App for InvoiceApp {
                                         if ui.add_enabled(!self.is_processing, button).clicked() {
                                             self.start_processing();
                                         }
+
+                                        // 停止按钮
+                                        if self.is_processing {
+                                            ui.add_space(8.0);
+                                            let stopping = self.cancel_token.as_ref().is_some_and(|t| t.is_cancelled());
+                                            let stop_button = egui::Button::new(
+                                                egui::RichText::new(if stopping { "⏳ 正在停止..." } else { "⏹ 停止" })
+                                                    .size(14.0)
+                                                    .color(TEXT_HIGH)
+                                            )
+                                            .fill(if stopping { TEXT_LOW } else { ACCENT_DANGER })
+                                            .rounding(egui::Rounding::same(8.0))
+                                            .min_size(egui::vec2(ui.available_width(), 36.0));
+
+                                            if ui.add_enabled(!stopping, stop_button).clicked() {
+                                                self.stop_processing();
+                                            }
+                                        }
+
+                                        // 进度条
+                                        if self.is_processing && self.progress_total > 0 {
+                                            ui.add_space(12.0);
+                                            let fraction = self.progress_done as f32 / self.progress_total as f32;
+                                            ui.add(
+                                                egui::ProgressBar::new(fraction)
+                                                    .fill(ACCENT_TECH)
+                                                    .text(egui::RichText::new(self.progress_text()).size(11.0).color(TEXT_HIGH))
+                                            );
+                                        }
                                     });
                             });
                             
@@ -487,10 +672,8 @@ impl eframe::App for InvoiceApp {
                                             ui.label(egui::RichText::new("运行日志").size(15.0).color(TEXT_HIGH).strong());
                                             
                                             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                                                if !self.log_messages.is_empty() {
-                                                    if ui.small_button("清除").clicked() {
-                                                        self.log_messages.clear();
-                                                    }
+                                                if !self.log_messages.is_empty() && ui.small_button("清除").clicked() {
+                                                    self.log_messages.clear();
                                                 }
                                             });
                                         });
@@ -590,6 +773,7 @@ impl eframe::App for InvoiceApp {
                                                 .column(Column::initial(150.0).at_least(120.0))  // 购买方
                                                 .column(Column::initial(150.0).at_least(120.0))  // 销售方
                                                 .column(Column::initial(100.0).at_least(80.0))   // 金额
+                                                .column(Column::initial(160.0).at_least(80.0))   // 二维码核对
                                                 .column(Column::initial(120.0).at_least(100.0))  // 备注
                                                 .header(28.0, |mut header| {
                                                     header.col(|ui| {
@@ -613,41 +797,63 @@ impl eframe::App for InvoiceApp {
                                                     header.col(|ui| {
                                                         ui.strong("金额");
                                                     });
+                                                    header.col(|ui| {
+                                                        ui.strong("二维码核对");
+                                                    });
                                                     header.col(|ui| {
                                                         ui.strong("备注");
                                                     });
                                                 })
                                                 .body(|mut body| {
                                                     for (idx, inv) in self.result_data.iter().enumerate() {
+                                                        // 文本与二维码不一致的发票整行标红
+                                                        let tampered = !inv.info.qr_mismatch.is_empty();
                                                         body.row(24.0, |mut row| {
-                                                            row.col(|ui| {
+                                                            Self::table_cell(&mut row, tampered, |ui| {
                                                                 ui.label(egui::RichText::new((idx + 1).to_string()).size(11.0).color(TEXT_LOW));
                                                             });
-                                                            row.col(|ui| {
+                                                            Self::table_cell(&mut row, tampered, |ui| {
                                                                 ui.label(egui::RichText::new(&inv.filename).size(11.0).color(TEXT_HIGH));
                                                             });
-                                                            row.col(|ui| {
+                                                            Self::table_cell(&mut row, tampered, |ui| {
                                                                 ui.label(egui::RichText::new(&inv.info.invoice_number).size(11.0).color(TEXT_MEDIUM).family(egui::FontFamily::Monospace));
                                                             });
-                                                            row.col(|ui| {
+                                                            Self::table_cell(&mut row, tampered, |ui| {
                                                                 ui.label(egui::RichText::new(&inv.info.invoice_date).size(11.0).color(TEXT_MEDIUM));
                                                             });
-                                                            row.col(|ui| {
+                                                            Self::table_cell(&mut row, tampered, |ui| {
                                                                 ui.label(egui::RichText::new(&inv.info.buyer).size(11.0).color(TEXT_MEDIUM));
                                                             });
-                                                            row.col(|ui| {
+                                                            Self::table_cell(&mut row, tampered, |ui| {
                                                                 ui.label(egui::RichText::new(&inv.info.seller).size(11.0).color(TEXT_MEDIUM));
                                                             });
-                                                            row.col(|ui| {
+                                                            Self::table_cell(&mut row, tampered, |ui| {
                                                                 let amount_color = if inv.info.amount.is_empty() {
                                                                     TEXT_LOW
+                                                                } else if inv.info.amount_mismatch {
+                                                                    ACCENT_DANGER
                                                                 } else {
                                                                     ACCENT_SUCCESS
                                                                 };
                                                                 ui.label(egui::RichText::new(&inv.info.amount).size(11.0).color(amount_color).family(egui::FontFamily::Monospace));
                                                             });
-                                                            row.col(|ui| {
-                                                                ui.label(egui::RichText::new(&inv.info.remark).size(11.0).color(TEXT_LOW));
+                                                            Self::table_cell(&mut row, tampered, |ui| {
+                                                                let (text, color) = if tampered {
+                                                                    let fields: Vec<&str> = inv.info.qr_mismatch.iter().map(|m| m.field.as_str()).collect();
+                                                                    (format!("不一致: {}", fields.join("、")), ACCENT_DANGER)
+                                                                } else if inv.info.qr_verified {
+                                                                    ("一致".to_string(), ACCENT_SUCCESS)
+                                                                } else {
+                                                                    (String::new(), TEXT_LOW)
+                                                                };
+                                                                let label = ui.label(egui::RichText::new(text).size(11.0).color(color));
+                                                                if tampered {
+                                                                    let details: Vec<String> = inv.info.qr_mismatch.iter().map(|m| m.to_string()).collect();
+                                                                    label.on_hover_text(details.join("\n"));
+                                                                }
+                                                            });
+                                                            Self::table_cell(&mut row, tampered, |ui| {
+                                                                ui.label(egui::RichText::new(inv.remark_text()).size(11.0).color(TEXT_LOW));
                                                             });
                                                         });
                                                     }
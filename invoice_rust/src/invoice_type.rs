@@ -0,0 +1,108 @@
+//! 发票类型识别
+//!
+//! 优先按票面标题判断；标题没有被完整提取时，再根据发票号码的格式
+//! （全电发票20位号码，旧版电子发票12位代码 + 8位号码）和"专用发票"、
+//! "普通发票"字样推断。
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::extractor::InvoiceInfo;
+
+/// 发票类型
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceType {
+    /// 电子发票（增值税专用发票），即全电专票
+    ElectronicSpecial,
+    /// 电子发票（普通发票），即全电普票
+    ElectronicNormal,
+    /// 增值税电子专用发票
+    VatElectronicSpecial,
+    /// 增值税电子普通发票
+    VatElectronicNormal,
+    /// 增值税专用发票（纸质）
+    VatSpecial,
+    /// 增值税普通发票（纸质）
+    VatNormal,
+    /// 增值税普通发票（卷票）
+    VatRoll,
+    /// 是发票但无法判断类型，未处理的文件（如图片）也是此类型
+    #[default]
+    Unknown,
+    /// 文本中没有发票号码和"发票"字样
+    NotInvoice,
+}
+
+/// 票面标题与类型，按顺序匹配，长标题在前
+const TITLES: [(&str, InvoiceType); 7] = [
+    ("电子发票(增值税专用发票)", InvoiceType::ElectronicSpecial),
+    ("电子发票(普通发票)", InvoiceType::ElectronicNormal),
+    ("增值税电子专用发票", InvoiceType::VatElectronicSpecial),
+    ("增值税电子普通发票", InvoiceType::VatElectronicNormal),
+    ("卷票", InvoiceType::VatRoll),
+    ("增值税专用发票", InvoiceType::VatSpecial),
+    ("增值税普通发票", InvoiceType::VatNormal),
+];
+
+impl InvoiceType {
+    /// 中文名称，用于表格导出
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::ElectronicSpecial => "电子发票(增值税专用发票)",
+            Self::ElectronicNormal => "电子发票(普通发票)",
+            Self::VatElectronicSpecial => "增值税电子专用发票",
+            Self::VatElectronicNormal => "增值税电子普通发票",
+            Self::VatSpecial => "增值税专用发票",
+            Self::VatNormal => "增值税普通发票",
+            Self::VatRoll => "增值税普通发票(卷票)",
+            Self::Unknown => "未知",
+            Self::NotInvoice => "非发票",
+        }
+    }
+
+    /// 是否为专用发票（可抵扣进项税）
+    pub fn is_special(self) -> bool {
+        matches!(
+            self,
+            Self::ElectronicSpecial | Self::VatElectronicSpecial | Self::VatSpecial
+        )
+    }
+
+    /// 根据发票文本和已识别的号码判断类型
+    pub(crate) fn classify(text: &str, info: &InvoiceInfo) -> Self {
+        // 去掉空白并统一括号，pdf-extract 常在字之间插入空格或换行
+        let compact: String = text
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| match c {
+                '（' => '(',
+                '）' => ')',
+                c => c,
+            })
+            .collect();
+
+        if let Some((_, kind)) = TITLES.iter().find(|(title, _)| compact.contains(title)) {
+            return *kind;
+        }
+
+        let special = compact.contains("专用发票");
+        let normal = compact.contains("普通发票");
+        let electronic = info.invoice_number.len() == 20;
+        let old_electronic = !info.invoice_code.is_empty();
+        match (electronic, old_electronic, special, normal) {
+            (true, _, true, _) => Self::ElectronicSpecial,
+            (true, _, _, true) => Self::ElectronicNormal,
+            (_, true, true, _) => Self::VatElectronicSpecial,
+            (_, true, _, true) => Self::VatElectronicNormal,
+            _ if info.invoice_number.is_empty() && !compact.contains("发票") => Self::NotInvoice,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for InvoiceType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
@@ -0,0 +1,754 @@
+//! 带坐标的PDF文本
+//!
+//! `pdf_extract::extract_text` 只输出一串文本，左右并排的购买方、销售方区块和
+//! 明细表的各列会混在一起。这里在解析文本的同一遍中记录每段文字在页面上的位置，
+//! 字段识别可以按位置查询，如"名称标签右侧的文字"、"金额列下方的单元格"。
+//!
+//! 坐标以页面左上角为原点，单位为点（1/72英寸），y 向下增大。
+
+use pdf_extract::{MediaBox, OutputDev, OutputError, PlainTextOutput, Transform};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::error::{InvoiceError, Result};
+use crate::extractor::InvoiceInfo;
+use crate::parties::{Parties, Party};
+use crate::rules::RuleSet;
+use crate::tax_id::check_tax_id;
+
+/// 页面上连续的一段文字，遇到空格或明显的间距时断开
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextRun {
+    pub text: String,
+    /// 左边缘
+    pub x: f64,
+    /// 上边缘
+    pub y: f64,
+    pub width: f64,
+    /// 字号
+    pub height: f64,
+}
+
+impl TextRun {
+    /// 右边缘
+    pub fn right(&self) -> f64 {
+        self.x + self.width
+    }
+
+    /// 下边缘（基线）
+    pub fn bottom(&self) -> f64 {
+        self.y + self.height
+    }
+
+    fn center_y(&self) -> f64 {
+        self.y + self.height / 2.0
+    }
+
+    /// 与另一段文字在同一行：垂直中心相差不到半个字高
+    pub fn same_line(&self, other: &TextRun) -> bool {
+        (self.center_y() - other.center_y()).abs() < self.height.min(other.height) / 2.0
+    }
+
+    /// 以冒号结尾，是"名称："之类的标签
+    fn is_label(&self) -> bool {
+        self.text.trim_end().ends_with([':', '：'])
+    }
+}
+
+/// 一页的文字及位置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PageLayout {
+    /// 页码，从1开始
+    pub number: u32,
+    pub width: f64,
+    pub height: f64,
+    /// 按内容流顺序排列的文字
+    pub runs: Vec<TextRun>,
+}
+
+impl PageLayout {
+    /// 按阅读顺序分行，每行按 x 排序
+    pub fn lines(&self) -> Vec<Vec<&TextRun>> {
+        let mut runs: Vec<&TextRun> = self.runs.iter().collect();
+        runs.sort_by(|a, b| a.center_y().total_cmp(&b.center_y()).then(a.x.total_cmp(&b.x)));
+
+        let mut lines: Vec<Vec<&TextRun>> = Vec::new();
+        for run in runs {
+            match lines.last_mut() {
+                Some(line) if line[0].same_line(run) => line.push(run),
+                _ => lines.push(vec![run]),
+            }
+        }
+        for line in &mut lines {
+            line.sort_by(|a, b| a.x.total_cmp(&b.x));
+        }
+        lines
+    }
+
+    /// 按阅读顺序查找所有包含 `label` 的文字（忽略空白）
+    ///
+    /// 字间距较大的标签（如"金 额"）会被拆成多段，相邻几段拼起来等于标签时
+    /// 合并为一段返回。
+    pub fn find_all(&self, label: &str) -> Vec<TextRun> {
+        let label = compact(label);
+        if label.is_empty() {
+            return Vec::new();
+        }
+
+        let mut found = Vec::new();
+        for line in self.lines() {
+            let mut i = 0;
+            while i < line.len() {
+                let mut joined = String::new();
+                let mut matched = None;
+                for (j, run) in line.iter().enumerate().skip(i) {
+                    joined.push_str(&compact(&run.text));
+                    if joined.contains(&label) {
+                        matched = Some(j);
+                        break;
+                    }
+                    if !label.starts_with(&joined) {
+                        break;
+                    }
+                }
+                match matched {
+                    Some(j) => {
+                        found.push(merge(&line[i..=j]));
+                        i = j + 1;
+                    }
+                    None => i += 1,
+                }
+            }
+        }
+        found
+    }
+
+    /// 按阅读顺序查找第一个包含 `label` 的文字
+    pub fn find(&self, label: &str) -> Option<TextRun> {
+        self.find_all(label).into_iter().next()
+    }
+
+    /// 查找竖排的标签（每行一个字，如全电发票区块左侧的"购买方信息"），各字合并为一段返回
+    pub fn find_vertical(&self, label: &str) -> Option<TextRun> {
+        let chars: Vec<String> = compact(label).chars().map(String::from).collect();
+        let (first, rest) = chars.split_first()?;
+        self.runs
+            .iter()
+            .filter(|run| compact(&run.text) == *first)
+            .find_map(|start| {
+                let mut column = vec![start];
+                for c in rest {
+                    let above = *column.last().unwrap();
+                    // 下一个字在正下方，间距不到一个字高
+                    let next = self.runs.iter().find(|run| {
+                        compact(&run.text) == *c
+                            && (run.x - above.x).abs() < above.height / 2.0
+                            && run.y > above.y
+                            && run.y - above.bottom() < above.height
+                    })?;
+                    column.push(next);
+                }
+                Some(merge(&column))
+            })
+    }
+
+    /// 标签右侧同一行的文字，如 `right_of("名称")` 返回名称
+    pub fn right_of(&self, label: &str) -> Option<String> {
+        let run = self.find(label)?;
+        self.right_of_run(&run, label)
+    }
+
+    /// `label_run` 中标签之后的文字；标签单独成段时取右侧同一行的文字，
+    /// 多段之间用空格分隔，遇到下一个标签或较大的间距为止
+    pub fn right_of_run(&self, label_run: &TextRun, label: &str) -> Option<String> {
+        let own = compact(&label_run.text);
+        let label = compact(label);
+        let rest = own
+            .find(&label)
+            .map(|at| &own[at + label.len()..])
+            .unwrap_or("")
+            .trim_start_matches([':', '：']);
+        if !rest.is_empty() {
+            return Some(rest.to_string());
+        }
+
+        let mut right: Vec<&TextRun> = self
+            .runs
+            .iter()
+            .filter(|run| run.same_line(label_run))
+            // 合并标签的各段右边缘都不超过标签本身
+            .filter(|run| {
+                run.x >= label_run.right() - run.height / 2.0 && run.right() > label_run.right()
+            })
+            .collect();
+        right.sort_by(|a, b| a.x.total_cmp(&b.x));
+
+        let mut value = String::new();
+        let mut last_end = label_run.right();
+        for run in right {
+            let text = compact(&run.text);
+            let text = text.trim_start_matches([':', '：']);
+            if text.is_empty() {
+                last_end = run.right();
+                continue;
+            }
+            if run.is_label() || run.x - last_end > run.height * 3.0 {
+                break;
+            }
+            if !value.is_empty() {
+                value.push(' ');
+            }
+            value.push_str(text);
+            last_end = run.right();
+        }
+        (!value.is_empty()).then_some(value)
+    }
+
+    /// 表头下方、与表头水平重叠的文字，按从上到下排列，如 `column_under("金额")`
+    pub fn column_under(&self, header: &str) -> Vec<&TextRun> {
+        let Some(head) = self.find(header) else {
+            return Vec::new();
+        };
+        let mut column: Vec<&TextRun> = self
+            .runs
+            .iter()
+            .filter(|run| run.y >= head.bottom() - run.height / 2.0 && !run.same_line(&head))
+            .filter(|run| run.x < head.right() && run.right() > head.x)
+            .collect();
+        column.sort_by(|a, b| a.y.total_cmp(&b.y));
+        column
+    }
+}
+
+/// 去掉所有空白
+fn compact(text: &str) -> String {
+    text.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// 同一行相邻的几段合并为一段
+fn merge(runs: &[&TextRun]) -> TextRun {
+    let x = runs.iter().map(|r| r.x).fold(f64::INFINITY, f64::min);
+    let y = runs.iter().map(|r| r.y).fold(f64::INFINITY, f64::min);
+    let right = runs.iter().map(|r| r.right()).fold(f64::NEG_INFINITY, f64::max);
+    let bottom = runs.iter().map(|r| r.bottom()).fold(f64::NEG_INFINITY, f64::max);
+    TextRun {
+        text: runs.iter().map(|r| r.text.as_str()).collect(),
+        x,
+        y,
+        width: right - x,
+        height: bottom - y,
+    }
+}
+
+/// 记录每个字符的位置并拼成 `TextRun`
+#[derive(Default)]
+struct LayoutOutput {
+    pages: Vec<PageLayout>,
+    page: PageLayout,
+    run: Option<TextRun>,
+}
+
+impl LayoutOutput {
+    fn finish_run(&mut self) {
+        if let Some(run) = self.run.take() {
+            self.page.runs.push(run);
+        }
+    }
+}
+
+impl OutputDev for LayoutOutput {
+    fn begin_page(
+        &mut self,
+        page_num: u32,
+        media_box: &MediaBox,
+        _art_box: Option<(f64, f64, f64, f64)>,
+    ) -> std::result::Result<(), OutputError> {
+        self.page = PageLayout {
+            number: page_num,
+            width: media_box.urx - media_box.llx,
+            height: media_box.ury - media_box.lly,
+            runs: Vec::new(),
+        };
+        Ok(())
+    }
+
+    fn end_page(&mut self) -> std::result::Result<(), OutputError> {
+        self.finish_run();
+        self.pages.push(std::mem::take(&mut self.page));
+        Ok(())
+    }
+
+    fn output_character(
+        &mut self,
+        trm: &Transform,
+        width: f64,
+        _spacing: f64,
+        font_size: f64,
+        char: &str,
+    ) -> std::result::Result<(), OutputError> {
+        // 与 pdf-extract 的纯文本输出相同：翻转 y 轴，按变换后的面积计算字号
+        let size = (font_size * (trm.m11 + trm.m21) * font_size * (trm.m12 + trm.m22))
+            .abs()
+            .sqrt();
+        let x = trm.m31;
+        let y = self.page.height - trm.m32 - size;
+        let advance = width * size;
+
+        if char.trim().is_empty() {
+            self.finish_run();
+            return Ok(());
+        }
+
+        if let Some(run) = &mut self.run {
+            let continues = (run.y - y).abs() < size / 2.0
+                && x >= run.right() - size / 2.0
+                && x <= run.right() + size * 0.3;
+            if continues {
+                run.text.push_str(char);
+                run.width = (x + advance - run.x).max(run.width);
+                return Ok(());
+            }
+        }
+        self.finish_run();
+        self.run = Some(TextRun {
+            text: char.to_string(),
+            x,
+            y,
+            width: advance,
+            height: size,
+        });
+        Ok(())
+    }
+
+    fn begin_word(&mut self) -> std::result::Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_word(&mut self) -> std::result::Result<(), OutputError> {
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> std::result::Result<(), OutputError> {
+        Ok(())
+    }
+}
+
+/// 同时输出纯文本和带坐标的文字，PDF只需解析一遍
+struct TextAndLayout<'a> {
+    text: PlainTextOutput<&'a mut String>,
+    layout: LayoutOutput,
+}
+
+impl OutputDev for TextAndLayout<'_> {
+    fn begin_page(
+        &mut self,
+        page_num: u32,
+        media_box: &MediaBox,
+        art_box: Option<(f64, f64, f64, f64)>,
+    ) -> std::result::Result<(), OutputError> {
+        self.text.begin_page(page_num, media_box, art_box)?;
+        self.layout.begin_page(page_num, media_box, art_box)
+    }
+
+    fn end_page(&mut self) -> std::result::Result<(), OutputError> {
+        self.text.end_page()?;
+        self.layout.end_page()
+    }
+
+    fn output_character(
+        &mut self,
+        trm: &Transform,
+        width: f64,
+        spacing: f64,
+        font_size: f64,
+        char: &str,
+    ) -> std::result::Result<(), OutputError> {
+        self.text.output_character(trm, width, spacing, font_size, char)?;
+        self.layout.output_character(trm, width, spacing, font_size, char)
+    }
+
+    fn begin_word(&mut self) -> std::result::Result<(), OutputError> {
+        self.text.begin_word()?;
+        self.layout.begin_word()
+    }
+
+    fn end_word(&mut self) -> std::result::Result<(), OutputError> {
+        self.text.end_word()?;
+        self.layout.end_word()
+    }
+
+    fn end_line(&mut self) -> std::result::Result<(), OutputError> {
+        self.text.end_line()?;
+        self.layout.end_line()
+    }
+}
+
+/// 提取PDF每一页的文字及位置
+pub fn extract_layout(pdf_path: &Path) -> Result<Vec<PageLayout>> {
+    let doc = load_pdf(pdf_path)?;
+    read_pdf(pdf_path, &doc).map(|(_, pages)| pages)
+}
+
+/// 打开PDF，加密的PDF尝试用空密码解密
+pub(crate) fn load_pdf(pdf_path: &Path) -> Result<lopdf::Document> {
+    let pdf_error = |e: lopdf::Error| InvoiceError::from_pdf(pdf_path, OutputError::PdfError(e));
+
+    let mut doc = lopdf::Document::load(pdf_path).map_err(pdf_error)?;
+    if doc.is_encrypted() {
+        // 只有打开密码为空的PDF可以解密
+        doc.decrypt("").map_err(pdf_error)?;
+    }
+    Ok(doc)
+}
+
+/// 提取PDF的纯文本（与 `pdf_extract::extract_text` 相同）和每一页的文字位置
+pub(crate) fn read_pdf(pdf_path: &Path, doc: &lopdf::Document) -> Result<(String, Vec<PageLayout>)> {
+    let mut text = String::new();
+    let mut output = TextAndLayout {
+        text: PlainTextOutput::new(&mut text),
+        layout: LayoutOutput::default(),
+    };
+    pdf_extract::output_doc(doc, &mut output)
+        .map_err(|e| InvoiceError::from_pdf(pdf_path, e))?;
+    let pages = output.layout.pages;
+    Ok((text, pages))
+}
+
+/// 可用文本层至少含有的文字数
+const MIN_TEXT_CHARS: usize = 20;
+
+/// 提取出的文本是否可用
+///
+/// 扫描件没有文本层，或者只有扫描软件加上的页码、水印等几个字；缺少字体编码表的PDF
+/// 提取出的则多是私用区字符和替换字符。可用的文本层至少有 [`MIN_TEXT_CHARS`] 个文字（汉字、字母、数字），
+/// 且文字占非空白字符的一半以上。
+pub(crate) fn has_text_layer(text: &str) -> bool {
+    let mut total = 0;
+    let mut readable = 0;
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        total += 1;
+        let private_use = matches!(c, '\u{E000}'..='\u{F8FF}' | '\u{F0000}'..);
+        if c.is_alphanumeric() && !private_use {
+            readable += 1;
+        }
+    }
+    readable >= MIN_TEXT_CHARS && readable * 2 >= total
+}
+
+/// 区块标题，优先取竖排的
+fn block_header(page: &PageLayout, label: &str) -> Option<TextRun> {
+    page.find_vertical(label).or_else(|| page.find(label))
+}
+
+/// 后面紧跟冒号的标签，排除明细表头的"项目名称"等
+fn labels<'a>(page: &'a PageLayout, label: &'a str) -> impl Iterator<Item = TextRun> + 'a {
+    page.find_all(label).into_iter().filter(move |run| {
+        let text = compact(&run.text);
+        text.split_once(label)
+            .is_some_and(|(_, rest)| rest.starts_with([':', '：']))
+    })
+}
+
+/// 标签后的税号：去掉前面的符号，至少15位字母数字
+fn tax_value(value: Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(|v| v.trim_start_matches(|c: char| !c.is_ascii_alphanumeric()))
+        .filter(|v| v.len() >= 15 && v.bytes().all(|b| b.is_ascii_alphanumeric()))
+        .map(str::to_string)
+}
+
+/// 按"购买方"/"销售方"区块标题的位置识别双方，页面上找不到两个标题时返回 `None`
+///
+/// 标题在区块左侧：两个区块左右并排时（全电发票），标签属于左侧最近的标题所在的区块；
+/// 上下排列时（旧版发票），属于竖直方向上最近的标题所在的区块。
+/// 不依赖文本的先后顺序，左右区块在文本中交错时也能正确配对名称和税号。
+pub(crate) fn parties_by_position(page: &PageLayout) -> Option<Parties> {
+    let buyer_head = block_header(page, "购买方")?;
+    let seller_head = block_header(page, "销售方")?;
+    let side_by_side =
+        (buyer_head.x - seller_head.x).abs() > (buyer_head.center_y() - seller_head.center_y()).abs();
+
+    // 标签是否属于购买方区块，不在任何区块中时为 `None`
+    let in_buyer_block = |run: &TextRun| -> Option<bool> {
+        if side_by_side {
+            let tolerance = run.height / 2.0;
+            [(true, &buyer_head), (false, &seller_head)]
+                .into_iter()
+                .filter(|(_, head)| head.x <= run.x + tolerance)
+                .max_by(|(_, a), (_, b)| a.x.total_cmp(&b.x))
+                .map(|(is_buyer, _)| is_buyer)
+        } else {
+            // 到标题竖直范围的距离，超过标题高度的不在区块中
+            let distance = |head: &TextRun| {
+                (head.y - run.center_y()).max(run.center_y() - head.bottom()).max(0.0)
+            };
+            let (buyer, seller) = (distance(&buyer_head), distance(&seller_head));
+            let (is_buyer, nearest, head) = if buyer <= seller {
+                (true, buyer, &buyer_head)
+            } else {
+                (false, seller, &seller_head)
+            };
+            (nearest <= head.height).then_some(is_buyer)
+        }
+    };
+
+    // 每个区块取第一个标签的值，值为空（如个人购买方没有税号）时仍记为找到了标签
+    let assign = |label: &str| -> (Option<Option<String>>, Option<Option<String>>) {
+        let (mut buyer, mut seller) = (None, None);
+        for run in labels(page, label) {
+            let slot = match in_buyer_block(&run) {
+                Some(true) => &mut buyer,
+                Some(false) => &mut seller,
+                None => continue,
+            };
+            if slot.is_none() {
+                *slot = Some(page.right_of_run(&run, label));
+            }
+        }
+        (buyer, seller)
+    };
+
+    let (buyer_name, seller_name) = assign("名称");
+    let (buyer_tax, seller_tax) = assign("纳税人识别号");
+    let has_name_labels = buyer_name.is_some() || seller_name.is_some();
+    let has_tax_labels = buyer_tax.is_some() || seller_tax.is_some();
+    if !has_name_labels && !has_tax_labels {
+        return None;
+    }
+    Some(Parties {
+        buyer: Party {
+            name: buyer_name.flatten(),
+            tax_number: tax_value(buyer_tax.flatten()),
+        },
+        seller: Party {
+            name: seller_name.flatten(),
+            tax_number: tax_value(seller_tax.flatten()),
+        },
+        has_name_labels,
+        has_tax_labels,
+    })
+}
+
+/// 税率列中的税率，按出现顺序去重；页面上没有税率列时为空
+pub(crate) fn column_tax_rates(page: &PageLayout, rules: &RuleSet) -> Vec<String> {
+    let mut rates: Vec<String> = Vec::new();
+    for run in page.column_under("税率") {
+        for m in rules.tax_rate.find_iter(&run.text) {
+            if !rates.iter().any(|rate| rate == m.as_str()) {
+                rates.push(m.as_str().to_string());
+            }
+        }
+    }
+    rates
+}
+
+/// 按位置补全文本中没有识别出的购买方、销售方
+///
+/// 页面上找不到区块标题（[`parties_by_position`] 无结果）时的补充：购买方区块在左（全电发票）
+/// 或在上（旧版发票），按阅读顺序第一个"名称："属于购买方，第二个属于销售方，税号标签同理。
+pub(crate) fn fill_parties(info: &mut InvoiceInfo, pages: &[PageLayout]) {
+    let Some(page) = pages.first() else {
+        return;
+    };
+
+    let values = |label: &str| -> Vec<Option<String>> {
+        labels(page, label)
+            .take(2)
+            .map(|run| page.right_of_run(&run, label))
+            .collect()
+    };
+
+    let names = values("名称");
+    if let [buyer, seller] = names.as_slice() {
+        if info.buyer.is_empty() {
+            info.buyer = buyer.clone().unwrap_or_default();
+        }
+        if info.seller.is_empty() {
+            info.seller = seller.clone().unwrap_or_default();
+        }
+    }
+
+    let taxes = values("纳税人识别号");
+    if let [buyer, seller] = taxes.as_slice() {
+        if info.buyer_tax_number.is_empty() {
+            if let Some(tax) = tax_value(buyer.clone()) {
+                info.buyer_tax_number_check = Some(check_tax_id(&tax));
+                info.buyer_tax_number = tax;
+            }
+        }
+        if info.seller_tax_number.is_empty() {
+            if let Some(tax) = tax_value(seller.clone()) {
+                info.seller_tax_number_check = Some(check_tax_id(&tax));
+                info.seller_tax_number = tax;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 字号为10、每个字宽10的一段文字
+    fn run(text: &str, x: f64, y: f64) -> TextRun {
+        TextRun {
+            text: text.to_string(),
+            x,
+            y,
+            width: text.chars().count() as f64 * 10.0,
+            height: 10.0,
+        }
+    }
+
+    fn page(runs: Vec<TextRun>) -> PageLayout {
+        PageLayout {
+            number: 1,
+            width: 600.0,
+            height: 400.0,
+            runs,
+        }
+    }
+
+    /// 竖排的标签，每行一个字
+    fn vertical(text: &str, x: f64, y: f64) -> Vec<TextRun> {
+        text.chars()
+            .enumerate()
+            .map(|(i, c)| run(&c.to_string(), x, y + i as f64 * 12.0))
+            .collect()
+    }
+
+    #[test]
+    fn right_of_label() {
+        let page = page(vec![
+            run("名称：", 10.0, 10.0),
+            run("深圳市示例", 45.0, 10.0),
+            run("科技有限公司", 96.0, 10.0),
+            run("名称：", 300.0, 10.0),
+            run("上海晨光文具贸易有限公司", 335.0, 10.0),
+            run("纳税人识别号：91440300MA5G0L2Q3P", 10.0, 30.0),
+            run("开户行：", 10.0, 50.0),
+        ]);
+        // 遇到较大的间距为止，不取右侧区块的标签和值
+        assert_eq!(page.right_of("名称").as_deref(), Some("深圳市示例 科技有限公司"));
+        // 标签和值在同一段
+        assert_eq!(page.right_of("纳税人识别号").as_deref(), Some("91440300MA5G0L2Q3P"));
+        assert_eq!(page.right_of("开户行"), None);
+        assert_eq!(page.right_of("地址"), None);
+    }
+
+    #[test]
+    fn right_of_spaced_label() {
+        // "名 称："的字间距较大，拆成了两段
+        let page = page(vec![run("名", 10.0, 10.0), run("称：", 30.0, 10.0), run("某某有限公司", 60.0, 10.0)]);
+        assert_eq!(page.right_of("名称").as_deref(), Some("某某有限公司"));
+    }
+
+    #[test]
+    fn column_under_header() {
+        let page = page(vec![
+            run("项目名称", 10.0, 50.0),
+            run("金额", 200.0, 50.0),
+            run("税额", 300.0, 50.0),
+            run("¥97.09", 290.0, 90.0),
+            run("97.09", 195.0, 62.0),
+            run("*经营租赁*通行费", 10.0, 62.0),
+            run("2.91", 305.0, 62.0),
+            run("¥97.09", 190.0, 90.0),
+        ]);
+        let cells: Vec<&str> = page.column_under("金额").iter().map(|run| run.text.as_str()).collect();
+        assert_eq!(cells, ["97.09", "¥97.09"]);
+        assert!(page.column_under("单价").is_empty());
+    }
+
+    #[test]
+    fn finds_vertical_label() {
+        let mut runs = vertical("购买方信息", 10.0, 50.0);
+        runs.push(run("方", 100.0, 74.0));
+        let page = page(runs);
+        let found = page.find_vertical("购买方").unwrap();
+        assert_eq!(found.text, "购买方");
+        assert_eq!((found.x, found.y), (10.0, 50.0));
+        assert_eq!(page.find_vertical("销售方"), None);
+    }
+
+    #[test]
+    fn parties_side_by_side_interleaved() {
+        // 全电发票：左右并排，内容流中两个区块的标签交错出现
+        let mut runs = vec![
+            run("名称：", 30.0, 60.0),
+            run("名称：", 330.0, 60.0),
+            run("上海晨光文具贸易有限公司", 365.0, 60.0),
+            run("统一社会信用代码/纳税人识别号：", 330.0, 80.0),
+            run("91310115MA1H7XKQ5R", 490.0, 80.0),
+            run("深圳市示例科技有限公司", 65.0, 60.0),
+            run("统一社会信用代码/纳税人识别号：", 30.0, 80.0),
+            run("91440300MA5G0L2Q3P", 190.0, 80.0),
+        ];
+        runs.extend(vertical("销售方信息", 310.0, 50.0));
+        runs.extend(vertical("购买方信息", 10.0, 50.0));
+        let parties = parties_by_position(&page(runs)).unwrap();
+        assert_eq!(parties.buyer.name.as_deref(), Some("深圳市示例科技有限公司"));
+        assert_eq!(parties.buyer.tax_number.as_deref(), Some("91440300MA5G0L2Q3P"));
+        assert_eq!(parties.seller.name.as_deref(), Some("上海晨光文具贸易有限公司"));
+        assert_eq!(parties.seller.tax_number.as_deref(), Some("91310115MA1H7XKQ5R"));
+        assert!(parties.has_name_labels && parties.has_tax_labels);
+    }
+
+    #[test]
+    fn parties_stacked_with_empty_tax_number() {
+        // 旧版发票：上下排列，个人购买方没有税号
+        let mut runs = vertical("销售方", 10.0, 200.0);
+        runs.extend(vertical("购买方", 10.0, 100.0));
+        runs.extend([
+            run("名称：", 30.0, 198.0),
+            run("上海晨光文具贸易有限公司", 65.0, 198.0),
+            run("纳税人识别号：", 30.0, 212.0),
+            run("91310115MA1H7XKQ5R", 105.0, 212.0),
+            run("名称：", 30.0, 98.0),
+            run("张三", 65.0, 98.0),
+            run("纳税人识别号：", 30.0, 112.0),
+            run("地址、电话：", 30.0, 126.0),
+            // 页脚的标签不在任何区块中
+            run("名称：", 30.0, 380.0),
+        ]);
+        let parties = parties_by_position(&page(runs)).unwrap();
+        assert_eq!(parties.buyer.name.as_deref(), Some("张三"));
+        assert_eq!(parties.buyer.tax_number, None);
+        assert_eq!(parties.seller.name.as_deref(), Some("上海晨光文具贸易有限公司"));
+        assert_eq!(parties.seller.tax_number.as_deref(), Some("91310115MA1H7XKQ5R"));
+        assert!(parties.has_tax_labels);
+    }
+
+    #[test]
+    fn parties_need_both_headers() {
+        let mut runs = vertical("购买方", 10.0, 100.0);
+        runs.push(run("名称：某某公司", 30.0, 98.0));
+        assert!(parties_by_position(&page(runs)).is_none());
+    }
+
+    #[test]
+    fn tax_rates_from_column() {
+        let page = page(vec![
+            run("项目名称", 10.0, 50.0),
+            run("税率/征收率", 300.0, 50.0),
+            run("*酒*酒精75%", 10.0, 62.0),
+            run("13%", 310.0, 62.0),
+            run("9%", 310.0, 74.0),
+            run("13%", 310.0, 86.0),
+        ]);
+        assert_eq!(column_tax_rates(&page, RuleSet::shared()), ["13%", "9%"]);
+    }
+
+    #[test]
+    fn text_layer_detection() {
+        let text = "电子发票（普通发票）\n发票号码：24312000000098765432\n名称：深圳市示例科技有限公司";
+        assert!(has_text_layer(text));
+        assert!(!has_text_layer(""));
+        // 扫描软件加上的页码
+        assert!(!has_text_layer("  第 1 页 / 共 1 页  \n"));
+        // 缺少字体编码表：私用区字符和替换字符占多数
+        let garbled: String = "\u{E001}\u{E002}\u{FFFD}".repeat(20) + "ABCDEFGHIJKLMNOPQRSTUV";
+        assert!(!has_text_layer(&garbled));
+    }
+}
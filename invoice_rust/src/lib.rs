@@ -0,0 +1,42 @@
+//! 发票识别核心库
+//!
+//! 提供PDF发票信息提取、批量处理和Excel导出功能，
+//! 桌面GUI与服务端任务共用同一套识别引擎。
+
+pub mod amount_words;
+pub mod batch;
+pub mod config;
+pub mod error;
+pub mod export;
+pub mod extractor;
+pub mod invoice_type;
+pub mod layout;
+pub mod line_items;
+#[cfg(feature = "ocr")]
+pub mod ocr;
+pub mod options;
+mod parties;
+mod pdf_images;
+pub mod qr;
+pub mod rules;
+pub mod tax_id;
+
+pub use batch::{collect_invoices, process_invoices, CancelToken, ProcessEvent};
+pub use config::{Config, DefaultsConfig, OcrConfig, RuleConfig};
+pub use error::InvoiceError;
+pub use export::{
+    export_all, generate_csv, generate_excel, generate_json, generate_jsonl, CsvEncoding,
+    CsvExporter, CsvOptions, ExportReport, Exporter, JsonExporter, XlsxExporter, XlsxOptions,
+};
+pub use extractor::{
+    extract_image_info, extract_image_info_with_rules, extract_invoice_info,
+    extract_invoice_info_with_rules, parse_invoice_text, InvoiceFile, InvoiceInfo, ProcessResult,
+    ProcessSummary,
+};
+pub use invoice_type::InvoiceType;
+pub use layout::{extract_layout, PageLayout, TextRun};
+pub use line_items::LineItem;
+pub use options::{EventCallback, ProcessOptions, ProcessOptionsBuilder, SortOrder};
+pub use qr::{read_qr_image, InvoiceQr, QrMismatch};
+pub use rules::RuleSet;
+pub use tax_id::TaxIdCheck;
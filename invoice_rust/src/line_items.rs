@@ -0,0 +1,127 @@
+//! 货物或应税劳务、服务明细
+//!
+//! pdf-extract 输出的明细行形如：
+//!
+//! ```text
+//! *文具*中性笔 0.5mm 盒 10 17.699115044248 176.99 13% 23.01
+//! *文具*中性笔 折扣 -17.70 13% -2.30
+//! ```
+//!
+//! 从行尾依次取税额、税率、金额，再取数量和单价，剩下的是项目名称、规格型号和单位。
+//! 项目名称过长时会折到下一行，因此一行解析不出时会与后续行合并再试。
+
+use serde::{Deserialize, Serialize};
+
+use crate::rules::RuleSet;
+
+/// 明细行
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LineItem {
+    /// 项目名称，含 `*类别*` 前缀
+    pub name: String,
+    pub spec: String,
+    pub unit: String,
+    pub quantity: String,
+    pub unit_price: String,
+    /// 金额（不含税），折扣行为负数
+    pub amount: String,
+    pub tax_rate: String,
+    /// 税额，免税时为 `***`
+    pub tax_amount: String,
+}
+
+impl LineItem {
+    /// 是否为折扣行（金额为负）
+    pub fn is_discount(&self) -> bool {
+        self.amount.starts_with('-')
+    }
+}
+
+/// 明细行最多跨越的文本行数
+const MAX_WRAPPED_LINES: usize = 3;
+
+/// 解析明细表，表头（含"规格型号"的行）到合计行之间的内容
+pub(crate) fn parse_line_items(text: &str, rules: &RuleSet) -> Vec<LineItem> {
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| !rules.item_table_header.is_match(line))
+        .skip(1)
+        .take_while(|line| !rules.item_table_end.is_match(line))
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    let mut items = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if !lines[i].starts_with('*') {
+            i += 1;
+            continue;
+        }
+
+        let mut joined = lines[i].to_string();
+        let mut parsed = parse_row(&joined, rules);
+        let mut used = 1;
+        while parsed.is_none()
+            && used < MAX_WRAPPED_LINES
+            && i + used < lines.len()
+            && !lines[i + used].starts_with('*')
+        {
+            // 折行的项目名称直接拼接，数字开头的续行用空格分隔
+            let next = lines[i + used];
+            if next.starts_with(|c: char| c.is_ascii_digit() || c == '-') {
+                joined.push(' ');
+            }
+            joined.push_str(next);
+            parsed = parse_row(&joined, rules);
+            used += 1;
+        }
+
+        match parsed {
+            Some(item) => {
+                items.push(item);
+                i += used;
+            }
+            None => i += 1,
+        }
+    }
+    items
+}
+
+/// 解析单个明细行，行尾不是"金额 税率 税额"时返回 `None`
+fn parse_row(row: &str, rules: &RuleSet) -> Option<LineItem> {
+    let mut tokens: Vec<&str> = row.split_whitespace().collect();
+    let is_number = |token: &str| rules.item_number.is_match(token);
+
+    let tax_amount = tokens.pop().filter(|t| is_number(t) || t.chars().all(|c| c == '*'))?;
+    let tax_rate = tokens.pop().filter(|t| rules.item_tax_rate.is_match(t))?;
+    let amount = tokens.pop().filter(|t| is_number(t))?;
+    if tokens.is_empty() {
+        return None;
+    }
+
+    let mut item = LineItem {
+        amount: amount.replace(',', ""),
+        tax_rate: tax_rate.to_string(),
+        tax_amount: tax_amount.replace(',', ""),
+        ..Default::default()
+    };
+
+    // 数量和单价成对出现，第一个是项目名称，不参与判断
+    if tokens.len() >= 3 && is_number(tokens[tokens.len() - 1]) && is_number(tokens[tokens.len() - 2]) {
+        item.unit_price = tokens.pop().unwrap().replace(',', "");
+        item.quantity = tokens.pop().unwrap().replace(',', "");
+
+        // 单位紧挨数量
+        if tokens.len() >= 2 {
+            item.unit = tokens.pop().unwrap().to_string();
+        }
+        item.name = tokens[0].to_string();
+        item.spec = tokens[1..].join(" ");
+    } else {
+        // 没有数量单价时（如折扣行、服务费）无法区分规格和单位，都算作名称
+        item.name = tokens.join(" ");
+    }
+
+    Some(item)
+}
@@ -1,4 +1,3 @@
-mod extractor;
 mod gui;
 
 fn main() -> Result<(), eframe::Error> {
@@ -22,7 +21,7 @@ fn main() -> Result<(), eframe::Error> {
 }
 
 fn setup_custom_fonts(ctx: &egui::Context) {
-    use egui::{FontFamily, FontId, FontData};
+    use egui::{FontFamily, FontId};
     
     let mut fonts = egui::FontDefinitions::default();
     
@@ -52,7 +51,6 @@ fn setup_custom_fonts(ctx: &egui::Context) {
 
 fn load_system_chinese_font(fonts: &mut egui::FontDefinitions) -> bool {
     use egui::{FontFamily, FontData};
-    use std::path::Path;
     
     // macOS 中文字体路径
     #[cfg(target_os = "macos")]
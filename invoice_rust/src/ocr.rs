@@ -0,0 +1,151 @@
+//! 图片文字识别
+//!
+//! 调用本机安装的 [Tesseract](https://github.com/tesseract-ocr/tesseract) 识别发票照片和扫描件PDF中的文字，
+//! 需要 `chi_sim` 语言包。识别出的文字与PDF文本一样提取各字段。
+//! 命令、语言和参数在配置文件的 `[ocr]` 中设置，见 [`OcrConfig`]。
+
+use image::GrayImage;
+use std::ffi::OsStr;
+use std::io::{Cursor, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::config::OcrConfig;
+use crate::error::{InvoiceError, Result};
+
+/// 识别图片文件中的文字
+pub fn recognize(image_path: &Path, config: &OcrConfig) -> Result<String> {
+    run(image_path.as_os_str(), None, image_path, config)
+}
+
+/// 识别内存中的图片（如扫描件PDF的页面），`source` 为出错时报告的文件
+pub fn recognize_image(image: &GrayImage, source: &Path, config: &OcrConfig) -> Result<String> {
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+        .map_err(|e| InvoiceError::Ocr {
+            path: source.to_path_buf(),
+            message: e.to_string(),
+        })?;
+    // 输入文件名为 stdin 时 Tesseract 从标准输入读取图片
+    run(OsStr::new("stdin"), Some(png), source, config)
+}
+
+/// 运行 Tesseract，输出写到标准输出
+fn run(input: &OsStr, stdin: Option<Vec<u8>>, source: &Path, config: &OcrConfig) -> Result<String> {
+    let failed = |message: String| InvoiceError::Ocr {
+        path: source.to_path_buf(),
+        message,
+    };
+
+    let mut child = Command::new(&config.command)
+        .arg(input)
+        .arg("stdout")
+        .arg("-l")
+        .arg(&config.languages)
+        .args(&config.args)
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => failed(format!(
+                "未找到 {}，请安装 Tesseract 或在配置文件的 [ocr] 中设置 command",
+                config.command
+            )),
+            _ => failed(e.to_string()),
+        })?;
+
+    // 在单独的线程中写入图片，同时读取输出：Tesseract 读完图片前写满了标准输出或
+    // 标准错误的管道（如大量警告）时，先写完再读取会互相等待
+    let writer = match (stdin, child.stdin.take()) {
+        (Some(data), Some(mut pipe)) => Some(thread::spawn(move || pipe.write_all(&data))),
+        _ => None,
+    };
+    let output = child.wait_with_output().map_err(|e| failed(e.to_string()))?;
+    // 以退出状态为准：没读完图片就退出时写入会失败（管道已关闭），但结果已由退出状态反映
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr.trim();
+        return Err(failed(if message.is_empty() {
+            output.status.to_string()
+        } else {
+            message.to_string()
+        }));
+    }
+    Ok(normalize(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// 去掉汉字之间的单个空格
+///
+/// 中文模型会在每个汉字之间输出空格，而识别规则按"发票号码"等连续的标签匹配；
+/// 保留连续的多个空格，它们通常分隔左右并排的区块。
+pub(crate) fn normalize(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut normalized = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        let single_space = c == ' '
+            && i > 0
+            && chars.get(i + 1).is_some_and(|next| !next.is_ascii() && !next.is_whitespace())
+            && !chars[i - 1].is_ascii()
+            && !chars[i - 1].is_whitespace();
+        if !single_space {
+            normalized.push(c);
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_single_spaces_between_chinese() {
+        assert_eq!(normalize("发 票 号 码 ： 12345678"), "发票号码： 12345678");
+        assert_eq!(normalize("开 票 日 期：2024年01月15日"), "开票日期：2024年01月15日");
+        // 连续的空格分隔左右并排的区块，保留
+        assert_eq!(normalize("购 买 方    销 售 方"), "购买方    销售方");
+        // 与ASCII字符之间、行首的空格保留
+        assert_eq!(normalize("金 额 123.45 元"), "金额 123.45 元");
+        assert_eq!(normalize("Invoice No 1\n 名 称"), "Invoice No 1\n 名称");
+        assert_eq!(normalize(""), "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_exiting_without_reading_image() {
+        // 图片远大于管道缓冲区，命令不读取标准输入就退出
+        let config = OcrConfig {
+            command: "true".to_string(),
+            ..OcrConfig::default()
+        };
+        let mut state = 1u32;
+        let image = GrayImage::from_fn(1000, 1000, |_, _| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            image::Luma([(state >> 24) as u8])
+        });
+        assert_eq!(recognize_image(&image, Path::new("scan.pdf"), &config).unwrap(), "");
+    }
+
+    #[test]
+    fn missing_command_is_reported() {
+        let config = OcrConfig {
+            command: "/nonexistent/tesseract".to_string(),
+            ..OcrConfig::default()
+        };
+        let image = GrayImage::new(4, 4);
+        match recognize_image(&image, Path::new("scan.pdf"), &config) {
+            Err(InvoiceError::Ocr { path, message }) => {
+                assert_eq!(path, Path::new("scan.pdf"));
+                assert!(message.contains("/nonexistent/tesseract"), "{}", message);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -0,0 +1,366 @@
+//! 批量处理选项
+//!
+//! GUI、命令行和库调用方共用同一套选项，通过 [`ProcessOptions::builder`] 构建。
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::batch::{CancelToken, ProcessEvent};
+use crate::config::{Config, OcrConfig, RuleConfig};
+use crate::error::{InvoiceError, Result};
+use crate::export::Exporter;
+use crate::rules::RuleSet;
+
+/// 处理事件回调，可能从多个工作线程同时调用
+pub type EventCallback = Arc<dyn Fn(&ProcessEvent) + Send + Sync>;
+
+/// 默认处理的文件类型
+pub const DEFAULT_FILE_TYPES: [&str; 4] = ["PDF", "PNG", "JPG", "JPEG"];
+
+/// 结果排序方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// 先按文件夹、再按文件名
+    #[default]
+    Path,
+    /// 只按文件名
+    FileName,
+    /// 保持目录遍历顺序
+    Unsorted,
+}
+
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "path" => Ok(Self::Path),
+            "name" | "filename" => Ok(Self::FileName),
+            "none" | "unsorted" => Ok(Self::Unsorted),
+            _ => Err(format!("不支持的排序方式: {}（可选 path、name、none）", s)),
+        }
+    }
+}
+
+/// 批量处理选项
+#[derive(Clone)]
+pub struct ProcessOptions {
+    pub(crate) buyer_keyword: Option<String>,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) file_types: Vec<String>,
+    pub(crate) include: Option<GlobSet>,
+    pub(crate) exclude: Option<GlobSet>,
+    pub(crate) skip_hidden: bool,
+    pub(crate) follow_links: bool,
+    pub(crate) sort: SortOrder,
+    pub(crate) workers: usize,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) rules: Option<Arc<RuleSet>>,
+    pub(crate) ocr: OcrConfig,
+    pub(crate) exporters: Vec<Arc<dyn Exporter>>,
+    pub(crate) on_event: Option<EventCallback>,
+    pub(crate) cancel: Option<CancelToken>,
+}
+
+impl Default for ProcessOptions {
+    fn default() -> Self {
+        Self {
+            buyer_keyword: None,
+            max_depth: None,
+            file_types: DEFAULT_FILE_TYPES.iter().map(|t| t.to_string()).collect(),
+            include: None,
+            exclude: None,
+            skip_hidden: true,
+            follow_links: false,
+            sort: SortOrder::default(),
+            workers: 0,
+            timeout: None,
+            rules: None,
+            ocr: OcrConfig::default(),
+            exporters: Vec::new(),
+            on_event: None,
+            cancel: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for ProcessOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProcessOptions")
+            .field("buyer_keyword", &self.buyer_keyword)
+            .field("max_depth", &self.max_depth)
+            .field("file_types", &self.file_types)
+            .field("skip_hidden", &self.skip_hidden)
+            .field("follow_links", &self.follow_links)
+            .field("sort", &self.sort)
+            .field("workers", &self.workers())
+            .field("timeout", &self.timeout)
+            .field(
+                "exporters",
+                &self.exporters.iter().map(|e| e.name()).collect::<Vec<_>>(),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl ProcessOptions {
+    pub fn builder() -> ProcessOptionsBuilder {
+        ProcessOptionsBuilder::default()
+    }
+
+    pub fn buyer_keyword(&self) -> Option<&str> {
+        self.buyer_keyword.as_deref()
+    }
+
+    /// 并行识别的线程数，未设置时为CPU核数
+    pub fn workers(&self) -> usize {
+        if self.workers > 0 {
+            self.workers
+        } else {
+            thread::available_parallelism().map_or(1, |n| n.get())
+        }
+    }
+
+    /// 单个文件的识别时间限制
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    /// 识别规则，未设置时为内置规则
+    pub fn rules(&self) -> &RuleSet {
+        match &self.rules {
+            Some(rules) => rules,
+            None => RuleSet::shared(),
+        }
+    }
+
+    /// 图片文字识别设置，只在启用 `ocr` 功能时使用
+    pub fn ocr(&self) -> &OcrConfig {
+        &self.ocr
+    }
+
+    pub fn exporters(&self) -> &[Arc<dyn Exporter>] {
+        &self.exporters
+    }
+
+    /// 是否已通过取消令牌取消
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(|token| token.is_cancelled())
+    }
+
+    /// 通知事件回调，没有回调时不构造事件
+    pub(crate) fn emit(&self, event: impl FnOnce() -> ProcessEvent) {
+        if let Some(on_event) = &self.on_event {
+            on_event(&event());
+        }
+    }
+
+    /// 文件扩展名（大写）是否在处理范围内
+    pub(crate) fn accepts_type(&self, file_ext: &str) -> bool {
+        self.file_types.iter().any(|t| t == file_ext)
+    }
+
+    /// 相对路径是否通过包含/排除规则
+    pub(crate) fn accepts_path(&self, rel_path: &Path) -> bool {
+        if let Some(include) = &self.include {
+            if !include.is_match(rel_path) {
+                return false;
+            }
+        }
+        match &self.exclude {
+            Some(exclude) => !exclude.is_match(rel_path),
+            None => true,
+        }
+    }
+}
+
+/// [`ProcessOptions`] 构建器
+#[derive(Default)]
+pub struct ProcessOptionsBuilder {
+    options: ProcessOptions,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    rule_config: Option<RuleConfig>,
+}
+
+impl ProcessOptionsBuilder {
+    /// 使用配置文件中的识别规则和默认选项，之后设置的选项覆盖配置中的默认值
+    ///
+    /// 配置中的跳过路径与 [`exclude`](Self::exclude) 添加的规则同时生效。
+    pub fn config(mut self, config: &Config) -> Self {
+        let defaults = &config.defaults;
+        self = self
+            .buyer_keyword(defaults.buyer_keyword.clone())
+            .file_types(&defaults.file_types);
+        self.options.max_depth = defaults.max_depth;
+        self.options.timeout = defaults.timeout.map(Duration::from_secs).filter(|t| !t.is_zero());
+        self.exclude.extend(defaults.exclude.iter().cloned());
+        self.rule_config = Some(config.rules.clone());
+        self.options.ocr = config.ocr.clone();
+        self
+    }
+
+    /// 购买方关键词，空字符串视为未设置
+    pub fn buyer_keyword(mut self, keyword: impl Into<String>) -> Self {
+        let keyword = keyword.into();
+        self.options.buyer_keyword = (!keyword.is_empty()).then_some(keyword);
+        self
+    }
+
+    /// 最大遍历深度，1 表示只处理目录下的文件，不进入子文件夹
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.options.max_depth = Some(depth);
+        self
+    }
+
+    /// 处理的文件类型（扩展名，不区分大小写），默认为 PDF、PNG、JPG、JPEG
+    pub fn file_types<I, S>(mut self, types: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.options.file_types = types
+            .into_iter()
+            .map(|t| t.as_ref().trim_start_matches('.').to_uppercase())
+            .collect();
+        self
+    }
+
+    /// 只处理匹配的文件，按相对发票目录的路径匹配（如 `2024-*/**`），可多次调用
+    pub fn include(mut self, pattern: impl Into<String>) -> Self {
+        self.include.push(pattern.into());
+        self
+    }
+
+    /// 跳过匹配的文件，按相对发票目录的路径匹配（如 `**/作废/**`），可多次调用
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    /// 是否跳过以 `.` 开头的隐藏文件，默认跳过
+    pub fn skip_hidden(mut self, skip: bool) -> Self {
+        self.options.skip_hidden = skip;
+        self
+    }
+
+    /// 是否跟随符号链接，默认不跟随
+    pub fn follow_links(mut self, follow: bool) -> Self {
+        self.options.follow_links = follow;
+        self
+    }
+
+    pub fn sort(mut self, sort: SortOrder) -> Self {
+        self.options.sort = sort;
+        self
+    }
+
+    /// 并行识别的线程数，0 表示使用CPU核数（默认），1 表示单线程
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.options.workers = workers;
+        self
+    }
+
+    /// 识别规则，默认为内置规则
+    pub fn rules(mut self, rules: RuleSet) -> Self {
+        self.options.rules = Some(Arc::new(rules));
+        self.rule_config = None;
+        self
+    }
+
+    /// 图片文字识别设置，只在启用 `ocr` 功能时使用
+    pub fn ocr(mut self, ocr: OcrConfig) -> Self {
+        self.options.ocr = ocr;
+        self
+    }
+
+    /// 单个文件的识别时间限制，超时的文件记为失败，为0时不限制，默认不限制
+    ///
+    /// 超时后不再等待该文件，但解析线程无法强制终止，会在后台运行到结束，
+    /// 其中正在运行的 Tesseract 进程也不会被结束。多个文件超时时这些线程和进程同时占用CPU和内存，
+    /// 处理大量扫描件时应留出足够的时间限制。
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.options.timeout = (!timeout.is_zero()).then_some(timeout);
+        self
+    }
+
+    /// 添加导出器，识别完成后按添加顺序执行
+    pub fn exporter(mut self, exporter: impl Exporter + 'static) -> Self {
+        self.options.exporters.push(Arc::new(exporter));
+        self
+    }
+
+    /// 接收处理进度事件，回调可能从多个工作线程同时调用
+    pub fn on_event(mut self, callback: impl Fn(&ProcessEvent) + Send + Sync + 'static) -> Self {
+        self.options.on_event = Some(Arc::new(callback));
+        self
+    }
+
+    /// 设置取消令牌，调用 [`CancelToken::cancel`] 后停止处理新的文件
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.options.cancel = Some(token);
+        self
+    }
+
+    /// 编译包含/排除规则和配置中的识别规则，规则无效时返回错误
+    pub fn build(mut self) -> Result<ProcessOptions> {
+        self.options.include = compile_globs(&self.include)?;
+        self.options.exclude = compile_globs(&self.exclude)?;
+        if let Some(config) = &self.rule_config {
+            self.options.rules = Some(Arc::new(RuleSet::from_config(config)?));
+        }
+        Ok(self.options)
+    }
+}
+
+/// 编译路径匹配规则（不区分大小写），没有规则时返回 `None`
+fn compile_globs(patterns: &[String]) -> Result<Option<GlobSet>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+
+    let invalid = |pattern: &str, e: globset::Error| InvoiceError::InvalidGlob {
+        pattern: pattern.to_string(),
+        message: e.kind().to_string(),
+    };
+
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = GlobBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| invalid(pattern, e))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map(Some)
+        .map_err(|e| invalid(&patterns.join(", "), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_from_config() {
+        let mut config = Config::default();
+        let options = ProcessOptions::builder().config(&config).build().unwrap();
+        assert_eq!(options.timeout(), None);
+
+        config.defaults.timeout = Some(120);
+        let options = ProcessOptions::builder().config(&config).build().unwrap();
+        assert_eq!(options.timeout(), Some(Duration::from_secs(120)));
+
+        // 之后设置的选项优先，0 表示不限
+        let options = ProcessOptions::builder().config(&config).timeout(Duration::ZERO).build().unwrap();
+        assert_eq!(options.timeout(), None);
+        config.defaults.timeout = Some(0);
+        let options = ProcessOptions::builder().config(&config).build().unwrap();
+        assert_eq!(options.timeout(), None);
+    }
+}
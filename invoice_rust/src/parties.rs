@@ -0,0 +1,225 @@
+//! 按标签识别购买方和销售方
+//!
+//! 票面上购买方、销售方各占一个区块，区块标题为"购买方信息"/"销售方信息"
+//! （旧版为竖排的"购买方"/"销售方"），区块内有"名称："和
+//! "统一社会信用代码/纳税人识别号："标签。标签落在哪个区块标题之后，
+//! 就属于哪一方，因此名称与同一区块中的税号配对，不依赖文本出现的先后。
+//!
+//! 有页面坐标时（PDF）先按区块标题的位置归属标签，见 [`parties_by_position`]。
+
+use regex::Captures;
+
+use crate::layout::{parties_by_position, PageLayout};
+use crate::rules::RuleSet;
+
+/// 一方的名称和税号
+#[derive(Debug, Default)]
+pub(crate) struct Party {
+    pub(crate) name: Option<String>,
+    pub(crate) tax_number: Option<String>,
+}
+
+/// 按标签识别出的双方信息
+#[derive(Debug, Default)]
+pub(crate) struct Parties {
+    pub(crate) buyer: Party,
+    pub(crate) seller: Party,
+    /// 找到了"名称："标签，此时名称为空的一方不再按商家关键词猜测
+    pub(crate) has_name_labels: bool,
+    /// 找到了税号标签，此时未识别出的税号（如个人购买方）应保持为空
+    pub(crate) has_tax_labels: bool,
+}
+
+/// 按标签识别双方，没有找到区块标题或"名称："、税号标签时返回 `None`
+///
+/// `page` 为发票第一页的文字位置，页面上找到了两个区块标题时以位置为准。
+pub(crate) fn parse_parties(text: &str, rules: &RuleSet, page: Option<&PageLayout>) -> Option<Parties> {
+    if let Some(parties) = page.and_then(parties_by_position) {
+        return Some(parties);
+    }
+
+    let buyer_at = rules.buyer_block.find(text)?.start();
+    let seller_at = rules.seller_block.find(text)?.start();
+
+    // 标签的位置和值，值为空（如个人购买方没有税号）时仍用于判断区块
+    let names: Vec<(usize, Option<String>)> = rules
+        .party_name
+        .captures_iter(text)
+        .map(|caps| (caps.get(0).unwrap().start(), label_value(text, &caps)))
+        .collect();
+    let taxes: Vec<(usize, Option<String>)> = rules
+        .party_tax_number
+        .captures_iter(text)
+        .map(|caps| (caps.get(0).unwrap().start(), label_value(text, &caps)))
+        .collect();
+    if names.is_empty() && taxes.is_empty() {
+        return None;
+    }
+
+    // 两个区块标题之间有标签时按区块归属；否则两个区块左右并排、标题先后出现，
+    // 文本中标签的先后不代表左右位置，只有同一行上的两个标签（如文字识别的结果）
+    // 能确定左侧的属于购买方，不在同一行时不归属任何一方
+    let (first_at, second_at) = (buyer_at.min(seller_at), buyer_at.max(seller_at));
+    let between = |pos: &usize| *pos > first_at && *pos < second_at;
+    let by_block = names.iter().chain(&taxes).any(|(pos, _)| between(pos));
+
+    let assign = |labels: &[(usize, Option<String>)]| -> (Option<String>, Option<String>) {
+        if by_block {
+            let in_block = |block_at: usize| {
+                labels
+                    .iter()
+                    .find(|(pos, _)| {
+                        *pos > block_at && (block_at == second_at || *pos < second_at)
+                    })
+                    .and_then(|(_, value)| value.clone())
+            };
+            (in_block(buyer_at), in_block(seller_at))
+        } else {
+            let line_of = |pos: usize| text[..pos].matches('\n').count();
+            let after: Vec<&(usize, Option<String>)> =
+                labels.iter().filter(|(pos, _)| *pos > second_at).collect();
+            match after.as_slice() {
+                [(left_at, left), (right_at, right), ..] if line_of(*left_at) == line_of(*right_at) => {
+                    (left.clone(), right.clone())
+                }
+                _ => (None, None),
+            }
+        }
+    };
+
+    let (buyer_name, seller_name) = assign(&names);
+    let (buyer_tax, seller_tax) = assign(&taxes);
+    Some(Parties {
+        buyer: Party {
+            name: buyer_name,
+            tax_number: buyer_tax,
+        },
+        seller: Party {
+            name: seller_name,
+            tax_number: seller_tax,
+        },
+        has_name_labels: !names.is_empty(),
+        has_tax_labels: !taxes.is_empty(),
+    })
+}
+
+/// 标签后的值；值后紧跟冒号说明标签为空、捕获到的是下一个标签
+fn label_value(text: &str, caps: &Captures) -> Option<String> {
+    let value = caps.get(1)?;
+    if text[value.end()..].starts_with([':', '：']) {
+        return None;
+    }
+    Some(value.as_str().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::TextRun;
+
+    fn parse(text: &str, page: Option<&PageLayout>) -> Parties {
+        parse_parties(text, RuleSet::shared(), page).unwrap()
+    }
+
+    fn names(parties: &Parties) -> (Option<&str>, Option<&str>) {
+        (parties.buyer.name.as_deref(), parties.seller.name.as_deref())
+    }
+
+    fn taxes(parties: &Parties) -> (Option<&str>, Option<&str>) {
+        (parties.buyer.tax_number.as_deref(), parties.seller.tax_number.as_deref())
+    }
+
+    /// 左右并排的两个区块，内容流中两侧的标签交错：名称、名称、右侧税号、左侧税号
+    const INTERLEAVED: &str = "购\n买\n方\n信\n息\n销\n售\n方\n信\n息\n\
+        名称：深圳市示例科技有限公司\n名称：上海晨光文具贸易有限公司\n\
+        统一社会信用代码/纳税人识别号：91310115MA1H7XKQ5R\n\
+        统一社会信用代码/纳税人识别号：91440300MA5G0L2Q3P\n";
+
+    fn run(text: &str, x: f64, y: f64) -> TextRun {
+        TextRun {
+            text: text.to_string(),
+            x,
+            y,
+            width: text.chars().count() as f64 * 10.0,
+            height: 10.0,
+        }
+    }
+
+    /// 与 [`INTERLEAVED`] 对应的页面：购买方区块在左，销售方区块在右
+    fn interleaved_page() -> PageLayout {
+        let mut runs = vec![
+            run("名称：", 30.0, 60.0),
+            run("深圳市示例科技有限公司", 65.0, 60.0),
+            run("名称：", 330.0, 60.0),
+            run("上海晨光文具贸易有限公司", 365.0, 60.0),
+            run("统一社会信用代码/纳税人识别号：", 330.0, 80.0),
+            run("91310115MA1H7XKQ5R", 490.0, 80.0),
+            run("统一社会信用代码/纳税人识别号：", 30.0, 80.0),
+            run("91440300MA5G0L2Q3P", 190.0, 80.0),
+        ];
+        for (x, header) in [(10.0, "购买方信息"), (310.0, "销售方信息")] {
+            for (i, c) in header.chars().enumerate() {
+                runs.push(run(&c.to_string(), x, 50.0 + i as f64 * 12.0));
+            }
+        }
+        PageLayout {
+            number: 1,
+            width: 600.0,
+            height: 400.0,
+            runs,
+        }
+    }
+
+    #[test]
+    fn labels_between_block_headers() {
+        let text = "购买方信息\n名称：深圳市示例科技有限公司\n\
+            统一社会信用代码/纳税人识别号：91440300MA5G0L2Q3P\n\
+            销售方信息\n名称：上海晨光文具贸易有限公司\n\
+            统一社会信用代码/纳税人识别号：91310115MA1H7XKQ5R\n";
+        let parties = parse(text, None);
+        assert_eq!(names(&parties), (Some("深圳市示例科技有限公司"), Some("上海晨光文具贸易有限公司")));
+        assert_eq!(taxes(&parties), (Some("91440300MA5G0L2Q3P"), Some("91310115MA1H7XKQ5R")));
+    }
+
+    #[test]
+    fn interleaved_labels_follow_page_position() {
+        let parties = parse(INTERLEAVED, Some(&interleaved_page()));
+        assert_eq!(names(&parties), (Some("深圳市示例科技有限公司"), Some("上海晨光文具贸易有限公司")));
+        assert_eq!(taxes(&parties), (Some("91440300MA5G0L2Q3P"), Some("91310115MA1H7XKQ5R")));
+    }
+
+    #[test]
+    fn interleaved_labels_without_position_stay_unassigned() {
+        // 只有文本时无法判断左右，不按先后猜测
+        let parties = parse(INTERLEAVED, None);
+        assert_eq!(names(&parties), (None, None));
+        assert_eq!(taxes(&parties), (None, None));
+        assert!(parties.has_name_labels && parties.has_tax_labels);
+    }
+
+    #[test]
+    fn side_by_side_labels_on_one_line() {
+        // 文字识别按行输出，左右区块的标签在同一行
+        let text = "购买方信息 销售方信息\n\
+            名称：深圳市示例科技有限公司    名称：上海晨光文具贸易有限公司\n\
+            统一社会信用代码/纳税人识别号：91440300MA5G0L2Q3P    统一社会信用代码/纳税人识别号：91310115MA1H7XKQ5R\n";
+        let parties = parse(text, None);
+        assert_eq!(names(&parties), (Some("深圳市示例科技有限公司"), Some("上海晨光文具贸易有限公司")));
+        assert_eq!(taxes(&parties), (Some("91440300MA5G0L2Q3P"), Some("91310115MA1H7XKQ5R")));
+    }
+
+    #[test]
+    fn empty_label_value() {
+        let text = "购买方信息\n名称：张三\n统一社会信用代码/纳税人识别号：\n\
+            销售方信息\n名称：上海晨光文具贸易有限公司\n\
+            统一社会信用代码/纳税人识别号：91310115MA1H7XKQ5R\n";
+        let parties = parse(text, None);
+        assert_eq!(names(&parties), (Some("张三"), Some("上海晨光文具贸易有限公司")));
+        assert_eq!(taxes(&parties), (None, Some("91310115MA1H7XKQ5R")));
+    }
+
+    #[test]
+    fn no_block_headers() {
+        assert!(parse_parties("名称：某某公司\n", RuleSet::shared(), None).is_none());
+    }
+}
@@ -0,0 +1,311 @@
+//! 读取PDF中嵌入的图片
+//!
+//! 只处理页面资源（包括表单XObject中）的图片XObject，转为灰度图供二维码识别和
+//! 扫描件的文字识别。
+//! 支持JPEG（DCTDecode）和未压缩、Flate、LZW 压缩的灰度、RGB、CMYK、索引色图片；
+//! JBIG2、CCITT 传真编码和 JPEG 2000 图片会被跳过。
+
+use image::GrayImage;
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use std::collections::HashSet;
+
+/// 图片像素数上限，避免畸形PDF声明的尺寸导致巨大的内存分配
+const MAX_PIXELS: u64 = 64 * 1024 * 1024;
+/// 表单XObject嵌套的最大层数
+const MAX_FORM_DEPTH: usize = 8;
+
+/// 按页面顺序列出所有图片XObject，重复引用的图片只出现一次
+pub(crate) fn image_ids(doc: &Document) -> Vec<ObjectId> {
+    let mut seen = HashSet::new();
+    doc.get_pages()
+        .into_values()
+        .flat_map(|page_id| page_image_ids(doc, page_id, &mut seen))
+        .collect()
+}
+
+/// 每一页上面积最大的图片，扫描件中即为整页的扫描图像；没有图片的页面不在结果中
+#[cfg(feature = "ocr")]
+pub(crate) fn page_scans(doc: &Document) -> Vec<ObjectId> {
+    let area = |id: &ObjectId| {
+        let Ok(stream) = doc.get_object(*id).and_then(Object::as_stream) else {
+            return 0;
+        };
+        let size = |key: &[u8]| stream.dict.get_deref(key, doc).and_then(Object::as_i64).unwrap_or(0);
+        // 尺寸来自PDF，可能是畸形的大数
+        size(b"Width").saturating_mul(size(b"Height"))
+    };
+    doc.get_pages()
+        .into_values()
+        .filter_map(|page_id| {
+            page_image_ids(doc, page_id, &mut HashSet::new())
+                .into_iter()
+                .max_by_key(area)
+        })
+        .collect()
+}
+
+/// 一页上的图片，跳过 `seen` 中已有的
+fn page_image_ids(doc: &Document, page_id: ObjectId, seen: &mut HashSet<ObjectId>) -> Vec<ObjectId> {
+    let mut ids = Vec::new();
+    let Ok((inline, resource_ids)) = doc.get_page_resources(page_id) else {
+        return ids;
+    };
+    let dicts = inline
+        .into_iter()
+        .chain(resource_ids.into_iter().filter_map(|id| doc.get_dictionary(id).ok()));
+    for resources in dicts {
+        collect_images(doc, resources, 0, seen, &mut ids);
+    }
+    ids
+}
+
+/// 收集资源字典中的图片，进入表单XObject继续查找
+fn collect_images(
+    doc: &Document,
+    resources: &Dictionary,
+    depth: usize,
+    seen: &mut HashSet<ObjectId>,
+    ids: &mut Vec<ObjectId>,
+) {
+    let Ok(xobjects) = resources.get_deref(b"XObject", doc).and_then(Object::as_dict) else {
+        return;
+    };
+    for (_, object) in xobjects.iter() {
+        let Ok(id) = object.as_reference() else {
+            continue;
+        };
+        if !seen.insert(id) {
+            continue;
+        }
+        let Ok(stream) = doc.get_object(id).and_then(Object::as_stream) else {
+            continue;
+        };
+        match stream.dict.get(b"Subtype").and_then(Object::as_name_str) {
+            Ok("Image") => ids.push(id),
+            Ok("Form") if depth < MAX_FORM_DEPTH => {
+                if let Ok(inner) = stream.dict.get_deref(b"Resources", doc).and_then(Object::as_dict) {
+                    collect_images(doc, inner, depth + 1, seen, ids);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 颜色空间
+enum ColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    /// 索引色：基础颜色空间和调色板
+    Indexed(Box<ColorSpace>, Vec<u8>),
+}
+
+impl ColorSpace {
+    fn components(&self) -> usize {
+        match self {
+            Self::Gray | Self::Indexed(..) => 1,
+            Self::Rgb => 3,
+            Self::Cmyk => 4,
+        }
+    }
+
+    /// 解析颜色空间，不支持的（如专色、Lab）返回 `None`
+    fn parse(doc: &Document, object: &Object) -> Option<Self> {
+        let (_, object) = doc.dereference(object).ok()?;
+        if let Ok(name) = object.as_name_str() {
+            return Self::from_name(name);
+        }
+
+        let array = object.as_array().ok()?;
+        let family = array.first()?.as_name_str().ok()?;
+        match family {
+            "ICCBased" => {
+                let (_, profile) = doc.dereference(array.get(1)?).ok()?;
+                match profile.as_stream().ok()?.dict.get(b"N").and_then(Object::as_i64).ok()? {
+                    1 => Some(Self::Gray),
+                    3 => Some(Self::Rgb),
+                    4 => Some(Self::Cmyk),
+                    _ => None,
+                }
+            }
+            "Indexed" | "I" => {
+                let base = Self::parse(doc, array.get(1)?)?;
+                let (_, lookup) = doc.dereference(array.get(3)?).ok()?;
+                let palette = match lookup {
+                    Object::String(bytes, _) => bytes.clone(),
+                    Object::Stream(stream) => stream_data(stream)?,
+                    _ => return None,
+                };
+                Some(Self::Indexed(Box::new(base), palette))
+            }
+            other => Self::from_name(other),
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "DeviceGray" | "G" | "CalGray" => Some(Self::Gray),
+            "DeviceRGB" | "RGB" | "CalRGB" => Some(Self::Rgb),
+            "DeviceCMYK" | "CMYK" => Some(Self::Cmyk),
+            _ => None,
+        }
+    }
+
+    /// 将各分量（0–255）转为亮度
+    fn luma(&self, values: &[u8]) -> u8 {
+        match self {
+            Self::Gray => values[0],
+            Self::Rgb => rgb_luma(values[0], values[1], values[2]),
+            Self::Cmyk => {
+                let k = values[3] as u16;
+                let channel = |c: u8| 255 - (c as u16 + k).min(255) as u8;
+                rgb_luma(channel(values[0]), channel(values[1]), channel(values[2]))
+            }
+            Self::Indexed(base, palette) => {
+                let n = base.components();
+                let start = values[0] as usize * n;
+                match palette.get(start..start + n) {
+                    Some(entry) => base.luma(entry),
+                    None => 255,
+                }
+            }
+        }
+    }
+}
+
+fn rgb_luma(r: u8, g: u8, b: u8) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+}
+
+/// 解压非图片的流（如索引色的调色板）
+fn stream_data(stream: &Stream) -> Option<Vec<u8>> {
+    match stream.filters() {
+        Ok(filters) if !filters.is_empty() => stream.decompressed_content().ok(),
+        _ => Some(stream.content.clone()),
+    }
+}
+
+/// 将图片XObject转为灰度图，不支持的编码或颜色空间返回 `None`
+pub(crate) fn to_gray(doc: &Document, id: ObjectId) -> Option<GrayImage> {
+    let stream = doc.get_object(id).and_then(Object::as_stream).ok()?;
+    let dict = &stream.dict;
+    let width = dict.get_deref(b"Width", doc).and_then(Object::as_i64).ok()?;
+    let height = dict.get_deref(b"Height", doc).and_then(Object::as_i64).ok()?;
+    let pixels = width.checked_mul(height)?;
+    if width <= 0 || height <= 0 || pixels as u64 > MAX_PIXELS {
+        return None;
+    }
+    let (width, height) = (width as u32, height as u32);
+
+    let filters = stream.filters().unwrap_or_default();
+    if filters.iter().any(|f| f == "DCTDecode" || f == "DCT") {
+        if filters.len() != 1 {
+            return None;
+        }
+        let image = image::load_from_memory_with_format(&stream.content, image::ImageFormat::Jpeg).ok()?;
+        return Some(image.to_luma8());
+    }
+
+    // lopdf 不解压图片流，去掉 Subtype 后按普通流解压
+    let data = if filters.is_empty() {
+        stream.content.clone()
+    } else {
+        let mut plain = stream.clone();
+        plain.dict.remove(b"Subtype");
+        plain.decompressed_content().ok()?
+    };
+
+    let image_mask = dict.get(b"ImageMask").and_then(Object::as_bool).unwrap_or(false);
+    let (color_space, bits) = if image_mask {
+        // 模板图片：0 表示用当前颜色（通常为黑色）绘制
+        (ColorSpace::Gray, 1)
+    } else {
+        let color_space = ColorSpace::parse(doc, dict.get(b"ColorSpace").ok()?)?;
+        let bits = dict.get_deref(b"BitsPerComponent", doc).and_then(Object::as_i64).unwrap_or(8);
+        (color_space, bits as usize)
+    };
+    if ![1, 2, 4, 8, 16].contains(&bits) {
+        return None;
+    }
+
+    // Decode 为 [1 0] 时黑白颠倒
+    let inverted = matches!(color_space, ColorSpace::Gray)
+        && dict
+            .get(b"Decode")
+            .and_then(Object::as_array)
+            .ok()
+            .and_then(|decode| decode.first()?.as_float().ok())
+            == Some(1.0);
+
+    let components = color_space.components();
+    let row_bytes = (width as usize * components * bits).div_ceil(8);
+    if data.len() < row_bytes * height as usize {
+        return None;
+    }
+    let max = (1u32 << bits.min(8)) - 1;
+    let indexed = matches!(color_space, ColorSpace::Indexed(..));
+
+    let mut pixels = Vec::with_capacity(width as usize * height as usize);
+    let mut values = vec![0u8; components];
+    for row in data.chunks(row_bytes).take(height as usize) {
+        for x in 0..width as usize {
+            for (c, value) in values.iter_mut().enumerate() {
+                let sample = read_sample(row, (x * components + c) * bits, bits);
+                // 索引色的值是调色板序号，不做缩放
+                *value = if indexed { sample as u8 } else { (sample * 255 / max) as u8 };
+            }
+            let luma = color_space.luma(&values);
+            pixels.push(if inverted { 255 - luma } else { luma });
+        }
+    }
+    GrayImage::from_raw(width, height, pixels)
+}
+
+/// 读取一行中从第 `offset` 比特开始的 `bits` 位样本，16位样本只取高8位
+fn read_sample(row: &[u8], offset: usize, bits: usize) -> u32 {
+    let byte = row[offset / 8] as u32;
+    if bits >= 8 {
+        return byte;
+    }
+    let shift = 8 - bits - offset % 8;
+    (byte >> shift) & ((1 << bits) - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    fn gray_image(width: i64, height: i64) -> (Document, ObjectId) {
+        let mut doc = Document::with_version("1.5");
+        let dict = dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => width,
+            "Height" => height,
+            "ColorSpace" => "DeviceGray",
+            "BitsPerComponent" => 8,
+        };
+        let id = doc.add_object(Stream::new(dict, vec![0; 4]));
+        (doc, id)
+    }
+
+    #[test]
+    fn converts_small_gray_image() {
+        let (doc, id) = gray_image(2, 2);
+        let image = to_gray(&doc, id).unwrap();
+        assert_eq!(image.dimensions(), (2, 2));
+    }
+
+    #[test]
+    fn rejects_overflowing_size() {
+        // 宽×高超出 i64 范围，不能回绕成一个小的正数
+        let (doc, id) = gray_image(i64::MAX / 2 + 1, 4);
+        assert!(to_gray(&doc, id).is_none());
+        let (doc, id) = gray_image(-(1 << 32), -(1 << 32));
+        assert!(to_gray(&doc, id).is_none());
+        let (doc, id) = gray_image(100_000, 100_000);
+        assert!(to_gray(&doc, id).is_none());
+    }
+}
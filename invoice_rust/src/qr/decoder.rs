@@ -0,0 +1,970 @@
+//! 二维码解码
+//!
+//! 面向PDF中嵌入的二维码图片和扫描件：找到三个定位图案后按仿射变换采样，
+//! 可以识别任意角度旋转和一定程度的错切、压缩，但不做透视校正，
+//! 近大远小的倾斜照片可能无法识别。支持版本1–40、全部纠错等级，
+//! 以及数字、字母数字、字节和汉字模式。
+
+use image::GrayImage;
+
+/// 放大后图片短边的最小像素数，嵌入PDF的二维码可能每个模块只有1像素
+const MIN_SIZE: u32 = 200;
+/// 放大后的最大像素数，细长的图片按短边放大会占用大量内存
+const MAX_UPSCALED_PIXELS: u64 = 4_000_000;
+/// 长边与短边之比的上限，更细长的图片（如分隔线、页眉条）不可能含有二维码；
+/// 整页扫描的发票约为 240:140
+const MAX_ASPECT_RATIO: u32 = 2;
+/// 四周补充的空白，贴边的二维码也能找到定位图案
+const MARGIN: usize = 16;
+/// 参与组合的定位图案候选数
+const MAX_CANDIDATES: usize = 6;
+
+/// 纠错码字数（每块），按纠错等级 L、M、Q、H 和版本排列
+const ECC_CODEWORDS_PER_BLOCK: [[u8; 41]; 4] = [
+    [0, 7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28, 30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28],
+    [0, 13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30, 28, 30, 30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+    [0, 17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28, 30, 24, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+];
+
+/// 纠错块数，按纠错等级 L、M、Q、H 和版本排列
+const NUM_ERROR_CORRECTION_BLOCKS: [[u8; 41]; 4] = [
+    [0, 1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13, 14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25],
+    [0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21, 23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49],
+    [0, 1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29, 34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68],
+    [0, 1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32, 35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81],
+];
+
+/// 字母数字模式的字符表
+const ALPHANUMERIC: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// 解码图片中的二维码，返回其中的文本；找不到或无法解码时返回 `None`
+pub(crate) fn decode(image: &GrayImage) -> Option<String> {
+    let (long, short) = (image.width().max(image.height()), image.width().min(image.height()));
+    if short == 0 || long > short * MAX_ASPECT_RATIO {
+        return None;
+    }
+    let bitmap = Bitmap::from_gray(image);
+    let finders = find_finders(&bitmap);
+    finder_triples(&finders)
+        .into_iter()
+        .find_map(|(tl, tr, bl)| decode_at(&bitmap, tl, tr, bl))
+}
+
+/// 二值化后的图片
+struct Bitmap {
+    width: usize,
+    height: usize,
+    dark: Vec<bool>,
+}
+
+impl Bitmap {
+    /// 按 Otsu 阈值二值化，小图按整数倍放大（不超过 [`MAX_UPSCALED_PIXELS`]），四周补空白
+    fn from_gray(image: &GrayImage) -> Self {
+        let threshold = otsu_threshold(image);
+        let short = image.width().min(image.height()).max(1);
+        let pixels = image.width() as u64 * image.height() as u64;
+        let mut scale = (MIN_SIZE / short).max(1) as u64;
+        while scale > 1 && pixels * scale * scale > MAX_UPSCALED_PIXELS {
+            scale -= 1;
+        }
+        let scale = scale as usize;
+        let width = image.width() as usize * scale + 2 * MARGIN;
+        let height = image.height() as usize * scale + 2 * MARGIN;
+
+        let mut dark = vec![false; width * height];
+        for (x, y, pixel) in image.enumerate_pixels() {
+            if pixel.0[0] > threshold {
+                continue;
+            }
+            for dy in 0..scale {
+                let row = (y as usize * scale + dy + MARGIN) * width;
+                for dx in 0..scale {
+                    dark[row + x as usize * scale + dx + MARGIN] = true;
+                }
+            }
+        }
+        Self { width, height, dark }
+    }
+
+    fn get(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.dark[y * self.width + x]
+    }
+
+    /// 按浮点坐标取像素，超出范围时为浅色
+    fn sample(&self, x: f64, y: f64) -> bool {
+        x >= 0.0 && y >= 0.0 && self.get(x as usize, y as usize)
+    }
+}
+
+/// Otsu 阈值：使深浅两类的类间方差最大，不大于阈值的像素为深色
+fn otsu_threshold(image: &GrayImage) -> u8 {
+    let mut histogram = [0u64; 256];
+    for pixel in image.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+    let total: u64 = histogram.iter().sum();
+    let sum: f64 = histogram.iter().enumerate().map(|(i, &n)| i as f64 * n as f64).sum();
+
+    let mut best = (0.0, 127u8);
+    let mut weight_dark = 0u64;
+    let mut sum_dark = 0.0;
+    for (t, &n) in histogram.iter().enumerate() {
+        weight_dark += n;
+        if weight_dark == 0 {
+            continue;
+        }
+        let weight_light = total - weight_dark;
+        if weight_light == 0 {
+            break;
+        }
+        sum_dark += t as f64 * n as f64;
+        let mean_dark = sum_dark / weight_dark as f64;
+        let mean_light = (sum - sum_dark) / weight_light as f64;
+        let between = weight_dark as f64 * weight_light as f64 * (mean_dark - mean_light).powi(2);
+        if between > best.0 {
+            best = (between, t as u8);
+        }
+    }
+    best.1
+}
+
+/// 定位图案（7×7的"回"字）的中心
+#[derive(Debug, Clone, Copy)]
+struct Finder {
+    x: f64,
+    y: f64,
+    /// 模块边长（像素）
+    module: f64,
+    /// 被多少行扫描确认
+    count: usize,
+}
+
+/// 深浅深浅深的宽度比例是否接近 1:1:3:1:1
+fn is_finder_ratio(counts: &[usize; 5]) -> bool {
+    let total: usize = counts.iter().sum();
+    if total < 7 {
+        return false;
+    }
+    let module = total as f64 / 7.0;
+    let tolerance = module / 2.0;
+    counts.iter().zip([1.0, 1.0, 3.0, 1.0, 1.0]).all(|(&count, units)| {
+        (count as f64 - module * units).abs() < tolerance * units
+    })
+}
+
+/// 逐行扫描查找定位图案，每个候选再沿竖直和水平方向确认
+fn find_finders(bitmap: &Bitmap) -> Vec<Finder> {
+    let mut finders: Vec<Finder> = Vec::new();
+    for y in 0..bitmap.height {
+        let mut counts = [0usize; 5];
+        let mut state = 0;
+        for x in 0..=bitmap.width {
+            if bitmap.get(x, y) {
+                if state % 2 == 1 {
+                    state += 1;
+                }
+                counts[state] += 1;
+            } else if state % 2 == 1 {
+                counts[state] += 1;
+            } else if state == 4 {
+                if is_finder_ratio(&counts) {
+                    let center = x as f64 - (counts[4] + counts[3]) as f64 - counts[2] as f64 / 2.0;
+                    if let Some(finder) = confirm_finder(bitmap, center, y, counts.iter().sum()) {
+                        add_finder(&mut finders, finder);
+                    }
+                }
+                // 后三段可能是下一个图案的前三段
+                counts = [counts[2], counts[3], counts[4], 1, 0];
+                state = 3;
+            } else if counts[state] > 0 {
+                state += 1;
+                counts[state] += 1;
+            }
+        }
+    }
+    finders
+}
+
+/// 在候选中心处沿竖直、水平方向各确认一次，返回修正后的中心
+fn confirm_finder(bitmap: &Bitmap, center_x: f64, y: usize, total: usize) -> Option<Finder> {
+    let x = center_x as usize;
+    let (center_y, vertical) = cross_check(bitmap, x, y, true, total)?;
+    let (center_x, horizontal) = cross_check(bitmap, x, center_y as usize, false, total)?;
+    Some(Finder {
+        x: center_x,
+        y: center_y,
+        module: (vertical + horizontal) as f64 / 14.0,
+        count: 1,
+    })
+}
+
+/// 从 (x, y) 沿一个方向向两侧数深浅段，比例符合时返回该方向上的中心和总宽度
+fn cross_check(
+    bitmap: &Bitmap,
+    x: usize,
+    y: usize,
+    vertical: bool,
+    expected: usize,
+) -> Option<(f64, usize)> {
+    let (start, len) = if vertical { (y, bitmap.height) } else { (x, bitmap.width) };
+    let at = |i: usize| if vertical { bitmap.get(x, i) } else { bitmap.get(i, y) };
+
+    let mut counts = [0usize; 5];
+    // 向前：中心深色段、浅色段、外圈深色段
+    let mut i = start;
+    for (state, dark) in [(2, true), (1, false), (0, true)] {
+        while at(i) == dark {
+            counts[state] += 1;
+            if i == 0 {
+                break;
+            }
+            i -= 1;
+        }
+    }
+    // 向后
+    let mut j = start + 1;
+    for (state, dark) in [(2, true), (3, false), (4, true)] {
+        while j < len && at(j) == dark {
+            counts[state] += 1;
+            j += 1;
+        }
+    }
+
+    let total: usize = counts.iter().sum();
+    // 错切或压缩后定位图案的宽、高可能相差较多
+    if counts.contains(&0) || 2 * total.abs_diff(expected) >= expected || !is_finder_ratio(&counts) {
+        return None;
+    }
+    let center = j as f64 - (counts[4] + counts[3]) as f64 - counts[2] as f64 / 2.0;
+    Some((center, total))
+}
+
+/// 与已有的候选重合时合并（按确认次数加权平均），否则新增
+fn add_finder(finders: &mut Vec<Finder>, finder: Finder) {
+    let same = finders.iter_mut().find(|f| {
+        (f.x - finder.x).abs() <= f.module
+            && (f.y - finder.y).abs() <= f.module
+            && (f.module - finder.module).abs() <= f.module.max(1.0)
+    });
+    match same {
+        Some(f) => {
+            let n = f.count as f64;
+            f.x = (f.x * n + finder.x) / (n + 1.0);
+            f.y = (f.y * n + finder.y) / (n + 1.0);
+            f.module = (f.module * n + finder.module) / (n + 1.0);
+            f.count += 1;
+        }
+        None => finders.push(finder),
+    }
+}
+
+/// 可能构成二维码的三个定位图案，按（左上、右上、左下）排列，确认次数多的组合在前
+fn finder_triples(finders: &[Finder]) -> Vec<(Finder, Finder, Finder)> {
+    let mut candidates: Vec<Finder> = finders.iter().copied().filter(|f| f.count >= 2).collect();
+    candidates.sort_by_key(|f| std::cmp::Reverse(f.count));
+    candidates.truncate(MAX_CANDIDATES);
+
+    let mut triples = Vec::new();
+    for i in 0..candidates.len() {
+        for j in i + 1..candidates.len() {
+            for k in j + 1..candidates.len() {
+                let (a, b, c) = (candidates[i], candidates[j], candidates[k]);
+                let modules = [a.module, b.module, c.module];
+                let max = modules.iter().copied().fold(f64::MIN, f64::max);
+                let min = modules.iter().copied().fold(f64::MAX, f64::min);
+                if max > min * 1.5 {
+                    continue;
+                }
+                if let Some(triple) = orient(a, b, c) {
+                    triples.push((a.count + b.count + c.count, triple));
+                }
+            }
+        }
+    }
+    triples.sort_by_key(|(count, _)| std::cmp::Reverse(*count));
+    triples.into_iter().map(|(_, triple)| triple).collect()
+}
+
+/// 最长边所对的点是左上角；两条直角边长度相近且夹角在60°–120°之间时返回排好的三个点
+fn orient(a: Finder, b: Finder, c: Finder) -> Option<(Finder, Finder, Finder)> {
+    let dist = |p: &Finder, q: &Finder| (p.x - q.x).hypot(p.y - q.y);
+    let (ab, bc, ca) = (dist(&a, &b), dist(&b, &c), dist(&c, &a));
+    let (tl, p, q) = if bc >= ab && bc >= ca {
+        (a, b, c)
+    } else if ca >= ab && ca >= bc {
+        (b, c, a)
+    } else {
+        (c, a, b)
+    };
+
+    let (v1, v2) = ((p.x - tl.x, p.y - tl.y), (q.x - tl.x, q.y - tl.y));
+    let (l1, l2) = (v1.0.hypot(v1.1), v2.0.hypot(v2.1));
+    let cos = (v1.0 * v2.0 + v1.1 * v2.1) / (l1 * l2);
+    if cos.abs() > 0.5 || l1.max(l2) > l1.min(l2) * 1.6 {
+        return None;
+    }
+    // 图片坐标 y 向下，右上角在左上→左下方向的逆时针一侧
+    let cross = v1.0 * v2.1 - v1.1 * v2.0;
+    Some(if cross > 0.0 { (tl, p, q) } else { (tl, q, p) })
+}
+
+/// 按估计的尺寸及相邻尺寸依次尝试采样和解码
+fn decode_at(bitmap: &Bitmap, tl: Finder, tr: Finder, bl: Finder) -> Option<String> {
+    let estimate = (modules_between(bitmap, tl, tr) + modules_between(bitmap, tl, bl)) / 2.0;
+    let estimate = estimate.round() as i64 + 7;
+    // 尺寸为 4×版本 + 17，取最接近的合法值
+    let nearest = estimate - (estimate - 1).rem_euclid(4) + if (estimate - 1).rem_euclid(4) > 2 { 4 } else { 0 };
+
+    [nearest, nearest - 4, nearest + 4]
+        .into_iter()
+        .filter(|size| (21..=177).contains(size))
+        .find_map(|size| {
+            let size = size as usize;
+            let grid = sample_grid(bitmap, tl, tr, bl, size);
+            decode_grid(&grid)
+        })
+}
+
+/// 两个定位图案中心之间的模块数
+///
+/// 旋转或错切后，逐行扫描测得的宽度不等于图案沿二维码边的宽度（旋转45°时相差√2倍），
+/// 所以沿两个中心的连线重新测量两个图案的宽度；测量失败时退回扫描得到的模块边长。
+fn modules_between(bitmap: &Bitmap, from: Finder, to: Finder) -> f64 {
+    let distance = (to.x - from.x).hypot(to.y - from.y);
+    let direction = ((to.x - from.x) / distance, (to.y - from.y) / distance);
+    let module = |f: Finder| finder_width(bitmap, f, direction).map_or(f.module, |width| width / 7.0);
+    distance / ((module(from) + module(to)) / 2.0)
+}
+
+/// 沿 `direction` 穿过定位图案中心测得的宽度（像素）
+fn finder_width(bitmap: &Bitmap, finder: Finder, direction: (f64, f64)) -> Option<f64> {
+    // 从中心向一侧依次经过中心深色块、浅色环、外圈深色环，到浅色为止
+    let half = |sign: f64| {
+        let limit = finder.module * 10.0;
+        let mut expect_dark = true;
+        let mut changes = 0;
+        let mut t = 0.0;
+        while t < limit {
+            let dark = bitmap.sample(finder.x + sign * t * direction.0, finder.y + sign * t * direction.1);
+            if dark != expect_dark {
+                changes += 1;
+                if changes == 3 {
+                    return Some(t);
+                }
+                expect_dark = dark;
+            }
+            t += 0.5;
+        }
+        None
+    };
+    Some(half(1.0)? + half(-1.0)?)
+}
+
+/// 以三个定位图案中心为基准，按仿射变换读取每个模块中心的颜色
+fn sample_grid(bitmap: &Bitmap, tl: Finder, tr: Finder, bl: Finder, size: usize) -> Vec<Vec<bool>> {
+    let span = size as f64 - 7.0;
+    let ex = ((tr.x - tl.x) / span, (tr.y - tl.y) / span);
+    let ey = ((bl.x - tl.x) / span, (bl.y - tl.y) / span);
+    (0..size)
+        .map(|row| {
+            (0..size)
+                .map(|col| {
+                    // 定位图案中心是第3.5个模块，模块中心在 +0.5 处
+                    let (u, v) = (col as f64 - 3.0, row as f64 - 3.0);
+                    bitmap.sample(tl.x + u * ex.0 + v * ey.0, tl.y + u * ex.1 + v * ey.1)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// 从模块矩阵解码出文本
+fn decode_grid(grid: &[Vec<bool>]) -> Option<String> {
+    let size = grid.len();
+    let version = (size - 17) / 4;
+    let (ecl, mask) = read_format(grid)?;
+    let function = function_modules(version, size);
+    let codewords = read_codewords(grid, &function, mask, version);
+    let data = correct_blocks(&codewords, version, ecl)?;
+    decode_segments(&data, version)
+}
+
+/// 格式信息（纠错等级和掩码）的15位编码
+fn format_bits(data: u32) -> u32 {
+    let mut rem = data;
+    for _ in 0..10 {
+        rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+    }
+    (data << 10 | rem) ^ 0x5412
+}
+
+/// 读取两份格式信息，取汉明距离最小的合法值，返回（纠错等级序号 L=0…H=3，掩码）
+fn read_format(grid: &[Vec<bool>]) -> Option<(usize, u8)> {
+    let size = grid.len();
+    let bit = |x: usize, y: usize| grid[y][x] as u32;
+
+    let mut first = 0;
+    for i in 0..6 {
+        first |= bit(8, i) << i;
+    }
+    first |= bit(8, 7) << 6 | bit(8, 8) << 7 | bit(7, 8) << 8;
+    for i in 9..15 {
+        first |= bit(14 - i, 8) << i;
+    }
+    let mut second = 0;
+    for i in 0..8 {
+        second |= bit(size - 1 - i, 8) << i;
+    }
+    for i in 8..15 {
+        second |= bit(8, size - 15 + i) << i;
+    }
+
+    let (distance, data) = (0..32u32)
+        .map(|data| {
+            let bits = format_bits(data);
+            let distance = ((bits ^ first).count_ones()).min((bits ^ second).count_ones());
+            (distance, data)
+        })
+        .min()?;
+    if distance > 3 {
+        return None;
+    }
+    // 格式信息中的纠错等级编码：L=01、M=00、Q=11、H=10
+    let ecl = match data >> 3 {
+        1 => 0,
+        0 => 1,
+        3 => 2,
+        _ => 3,
+    };
+    Some((ecl, (data & 7) as u8))
+}
+
+/// 校正图形的中心坐标
+fn alignment_positions(version: usize, size: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let count = version / 7 + 2;
+    let step = if version == 32 {
+        26
+    } else {
+        (version * 4 + count * 2 + 1) / (count * 2 - 2) * 2
+    };
+    let mut positions: Vec<usize> = (0..count - 1).map(|i| size - 7 - i * step).collect();
+    positions.push(6);
+    positions.reverse();
+    positions
+}
+
+/// 功能图形（定位、分隔符、定时、校正、格式和版本信息）占用的模块
+fn function_modules(version: usize, size: usize) -> Vec<Vec<bool>> {
+    let mut function = vec![vec![false; size]; size];
+    let mut mark = |x: usize, y: usize| function[y][x] = true;
+
+    for i in 0..size {
+        mark(6, i);
+        mark(i, 6);
+    }
+    // 定位图案及分隔符，连同格式信息区域
+    for y in 0..9 {
+        for x in 0..9 {
+            mark(x, y);
+        }
+        for x in size - 8..size {
+            mark(x, y);
+        }
+    }
+    for y in size - 8..size {
+        for x in 0..9 {
+            mark(x, y);
+        }
+    }
+
+    let positions = alignment_positions(version, size);
+    let n = positions.len();
+    for (i, &cx) in positions.iter().enumerate() {
+        for (j, &cy) in positions.iter().enumerate() {
+            // 与定位图案重叠的三个角不放校正图形
+            if (i == 0 || j == 0) && (i + j == 0 || i + j == n - 1) {
+                continue;
+            }
+            for y in cy - 2..=cy + 2 {
+                for x in cx - 2..=cx + 2 {
+                    mark(x, y);
+                }
+            }
+        }
+    }
+
+    if version >= 7 {
+        for i in 0..18 {
+            let (a, b) = (size - 11 + i % 3, i / 3);
+            mark(a, b);
+            mark(b, a);
+        }
+    }
+    function
+}
+
+/// 掩码图案在 (x, y) 处是否翻转
+fn mask_bit(mask: u8, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (x + y).is_multiple_of(3),
+        4 => (x / 3 + y / 2).is_multiple_of(2),
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3).is_multiple_of(2),
+        _ => ((x + y) % 2 + x * y % 3).is_multiple_of(2),
+    }
+}
+
+/// 数据区的码字数（含纠错码字）
+fn raw_codewords(version: usize) -> usize {
+    let mut modules = (16 * version + 128) * version + 64;
+    if version >= 2 {
+        let count = version / 7 + 2;
+        modules -= (25 * count - 10) * count - 55;
+        if version >= 7 {
+            modules -= 36;
+        }
+    }
+    modules / 8
+}
+
+/// 从右下角开始按两列一组之字形读取数据区，去掉掩码
+fn read_codewords(grid: &[Vec<bool>], function: &[Vec<bool>], mask: u8, version: usize) -> Vec<u8> {
+    let size = grid.len();
+    let total = raw_codewords(version);
+    let mut codewords = vec![0u8; total];
+    let mut i = 0;
+
+    let mut right = size - 1;
+    while right >= 1 {
+        if right == 6 {
+            right = 5;
+        }
+        let upward = (right + 1) & 2 == 0;
+        for vert in 0..size {
+            let y = if upward { size - 1 - vert } else { vert };
+            for x in [right, right - 1] {
+                if function[y][x] || i >= total * 8 {
+                    continue;
+                }
+                if grid[y][x] ^ mask_bit(mask, x, y) {
+                    codewords[i >> 3] |= 0x80 >> (i & 7);
+                }
+                i += 1;
+            }
+        }
+        if right < 2 {
+            break;
+        }
+        right -= 2;
+    }
+    codewords
+}
+
+/// 按块拆分交织的码字，逐块纠错后拼接数据码字
+fn correct_blocks(codewords: &[u8], version: usize, ecl: usize) -> Option<Vec<u8>> {
+    let blocks = NUM_ERROR_CORRECTION_BLOCKS[ecl][version] as usize;
+    let ecc_len = ECC_CODEWORDS_PER_BLOCK[ecl][version] as usize;
+    let total = codewords.len();
+    let short_blocks = blocks - total % blocks;
+    let short_len = total / blocks;
+
+    // 短块在数据末尾少一个码字，交织时跳过该位置
+    let mut split = vec![Vec::with_capacity(short_len + 1); blocks];
+    let mut next = codewords.iter();
+    for i in 0..=short_len {
+        for (j, block) in split.iter_mut().enumerate() {
+            if i == short_len - ecc_len && j < short_blocks {
+                continue;
+            }
+            block.push(*next.next()?);
+        }
+    }
+
+    let mut data = Vec::new();
+    for mut block in split {
+        if !reed_solomon_correct(&mut block, ecc_len) {
+            return None;
+        }
+        data.extend_from_slice(&block[..block.len() - ecc_len]);
+    }
+    Some(data)
+}
+
+/// GF(256) 运算，本原多项式 x⁸+x⁴+x³+x²+1
+struct Galois {
+    exp: [u8; 512],
+    log: [u8; 256],
+}
+
+impl Galois {
+    fn new() -> Self {
+        let mut exp = [0u8; 512];
+        let mut log = [0u8; 256];
+        let mut x: u16 = 1;
+        for (i, value) in exp.iter_mut().take(255).enumerate() {
+            *value = x as u8;
+            log[x as usize] = i as u8;
+            x <<= 1;
+            if x & 0x100 != 0 {
+                x ^= 0x11D;
+            }
+        }
+        for i in 255..512 {
+            exp[i] = exp[i - 255];
+        }
+        Self { exp, log }
+    }
+
+    fn mul(&self, a: u8, b: u8) -> u8 {
+        if a == 0 || b == 0 {
+            0
+        } else {
+            self.exp[self.log[a as usize] as usize + self.log[b as usize] as usize]
+        }
+    }
+
+    fn div(&self, a: u8, b: u8) -> u8 {
+        if a == 0 {
+            0
+        } else {
+            self.exp[self.log[a as usize] as usize + 255 - self.log[b as usize] as usize]
+        }
+    }
+
+    /// α 的 `power` 次幂
+    fn pow(&self, power: usize) -> u8 {
+        self.exp[power % 255]
+    }
+
+    /// 多项式求值，系数按次数从低到高排列
+    fn eval(&self, poly: &[u8], x: u8) -> u8 {
+        poly.iter().rev().fold(0, |acc, &c| self.mul(acc, x) ^ c)
+    }
+}
+
+/// Reed-Solomon 纠错，码字第一个字节为最高次项；无法纠正时返回 `false`
+fn reed_solomon_correct(block: &mut [u8], ecc_len: usize) -> bool {
+    let gf = Galois::new();
+    let n = block.len();
+    let syndromes = |block: &[u8]| -> Vec<u8> {
+        (0..ecc_len)
+            .map(|i| block.iter().fold(0, |acc, &c| gf.mul(acc, gf.pow(i)) ^ c))
+            .collect()
+    };
+
+    let s = syndromes(block);
+    if s.iter().all(|&v| v == 0) {
+        return true;
+    }
+
+    // Berlekamp-Massey 求错误位置多项式 Λ
+    let mut lambda = vec![1u8];
+    let mut prev = vec![1u8];
+    let mut errors = 0;
+    let mut shift = 1;
+    let mut prev_discrepancy = 1u8;
+    for r in 0..ecc_len {
+        let mut d = s[r];
+        for i in 1..=errors.min(lambda.len() - 1) {
+            d ^= gf.mul(lambda[i], s[r - i]);
+        }
+        if d == 0 {
+            shift += 1;
+            continue;
+        }
+        let coef = gf.div(d, prev_discrepancy);
+        let old = lambda.clone();
+        if lambda.len() < prev.len() + shift {
+            lambda.resize(prev.len() + shift, 0);
+        }
+        for (i, &p) in prev.iter().enumerate() {
+            lambda[i + shift] ^= gf.mul(coef, p);
+        }
+        if 2 * errors <= r {
+            errors = r + 1 - errors;
+            prev = old;
+            prev_discrepancy = d;
+            shift = 1;
+        } else {
+            shift += 1;
+        }
+    }
+    if 2 * errors > ecc_len {
+        return false;
+    }
+
+    // Chien 搜索：第 k 个字节对应 x 的 n-1-k 次项，错误位置为 Λ 的根的倒数
+    let positions: Vec<usize> = (0..n)
+        .filter(|&k| gf.eval(&lambda, gf.pow(255 - (n - 1 - k) % 255)) == 0)
+        .collect();
+    if positions.len() != errors {
+        return false;
+    }
+
+    // Forney 算法求错误值：Ω = S·Λ mod x^ecc_len，e = X·Ω(X⁻¹) / Λ'(X⁻¹)
+    let mut omega = vec![0u8; ecc_len];
+    for (i, &si) in s.iter().enumerate() {
+        for (j, &lj) in lambda.iter().enumerate() {
+            if i + j < ecc_len {
+                omega[i + j] ^= gf.mul(si, lj);
+            }
+        }
+    }
+    for &k in &positions {
+        let x = gf.pow(n - 1 - k);
+        let x_inv = gf.div(1, x);
+        let mut derivative = 0;
+        for i in (1..lambda.len()).step_by(2) {
+            let mut term = lambda[i];
+            for _ in 0..i - 1 {
+                term = gf.mul(term, x_inv);
+            }
+            derivative ^= term;
+        }
+        if derivative == 0 {
+            return false;
+        }
+        block[k] ^= gf.div(gf.mul(x, gf.eval(&omega, x_inv)), derivative);
+    }
+
+    syndromes(block).iter().all(|&v| v == 0)
+}
+
+/// 按位读取
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn remaining(&self) -> usize {
+        self.data.len() * 8 - self.pos
+    }
+
+    fn read(&mut self, bits: usize) -> Option<u32> {
+        if bits > self.remaining() {
+            return None;
+        }
+        let mut value = 0;
+        for _ in 0..bits {
+            let bit = self.data[self.pos >> 3] >> (7 - (self.pos & 7)) & 1;
+            value = value << 1 | bit as u32;
+            self.pos += 1;
+        }
+        Some(value)
+    }
+}
+
+/// 解析数据码字中的各段，字节模式优先按 UTF-8、否则按 GBK 解码
+///
+/// 有的编码器把 UTF-8 文本中恰好符合 Shift_JIS 的字节对编为汉字模式，
+/// 所以汉字模式的字节与前后的字节段合在一起判断编码，只有汉字模式时按 Shift_JIS 解码。
+fn decode_segments(data: &[u8], version: usize) -> Option<String> {
+    let size_class = match version {
+        1..=9 => 0,
+        10..=26 => 1,
+        _ => 2,
+    };
+    let mut reader = BitReader { data, pos: 0 };
+    let mut text = String::new();
+    let mut bytes: Vec<u8> = Vec::new();
+    // 缓冲的字节全部来自汉字模式
+    let mut kanji_only = true;
+
+    let flush = |bytes: &mut Vec<u8>, text: &mut String, kanji_only: &mut bool| {
+        if bytes.is_empty() {
+            return;
+        }
+        match std::str::from_utf8(bytes) {
+            Ok(s) => text.push_str(s),
+            Err(_) if *kanji_only => text.push_str(&encoding_rs::SHIFT_JIS.decode(bytes).0),
+            Err(_) => text.push_str(&encoding_rs::GBK.decode(bytes).0),
+        }
+        bytes.clear();
+        *kanji_only = true;
+    };
+
+    while reader.remaining() >= 4 {
+        match reader.read(4)? {
+            0 => break,
+            // 数字：每3位10比特
+            1 => {
+                flush(&mut bytes, &mut text, &mut kanji_only);
+                let mut count = reader.read([10, 12, 14][size_class])? as usize;
+                while count > 0 {
+                    let digits = count.min(3);
+                    let value = reader.read([4, 7, 10][digits - 1])?;
+                    text.push_str(&format!("{:0width$}", value, width = digits));
+                    count -= digits;
+                }
+            }
+            // 字母数字：每2个字符11比特
+            2 => {
+                flush(&mut bytes, &mut text, &mut kanji_only);
+                let mut count = reader.read([9, 11, 13][size_class])? as usize;
+                while count >= 2 {
+                    let value = reader.read(11)? as usize;
+                    text.push(*ALPHANUMERIC.get(value / 45)? as char);
+                    text.push(*ALPHANUMERIC.get(value % 45)? as char);
+                    count -= 2;
+                }
+                if count == 1 {
+                    text.push(*ALPHANUMERIC.get(reader.read(6)? as usize)? as char);
+                }
+            }
+            4 => {
+                kanji_only = false;
+                let count = reader.read([8, 16, 16][size_class])?;
+                for _ in 0..count {
+                    bytes.push(reader.read(8)? as u8);
+                }
+            }
+            // 汉字（Shift_JIS），每个字13比特
+            8 => {
+                let count = reader.read([8, 10, 12][size_class])?;
+                for _ in 0..count {
+                    let value = reader.read(13)?;
+                    let code = (value / 0xC0) << 8 | (value % 0xC0);
+                    let code = if code + 0x8140 <= 0x9FFC { code + 0x8140 } else { code + 0xC140 };
+                    bytes.extend_from_slice(&(code as u16).to_be_bytes());
+                }
+            }
+            // ECI：只跳过指定值，字节按内容自动判断编码
+            7 => {
+                let first = reader.read(8)?;
+                if first & 0x80 != 0 {
+                    reader.read(if first & 0x40 == 0 { 8 } else { 16 })?;
+                }
+            }
+            // 结构链接
+            3 => {
+                reader.read(16)?;
+            }
+            // FNC1
+            5 => {}
+            9 => {
+                reader.read(8)?;
+            }
+            _ => return None,
+        }
+    }
+    flush(&mut bytes, &mut text, &mut kanji_only);
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{imageops, Luma};
+    use qrcode::{Color, EcLevel, QrCode, Version};
+
+    const PAYLOAD: &str = "01,32,,24442000000012345678,1130.00,20240115,,A1B2,";
+
+    /// 按每模块 `scale` 像素渲染二维码，四周留4个模块的空白
+    fn render(code: &QrCode, scale: u32) -> GrayImage {
+        let width = code.width() as u32;
+        let colors = code.to_colors();
+        let size = (width + 8) * scale;
+        GrayImage::from_fn(size, size, |x, y| {
+            let (mx, my) = ((x / scale).wrapping_sub(4), (y / scale).wrapping_sub(4));
+            let dark = mx < width && my < width && colors[(my * width + mx) as usize] == Color::Dark;
+            Luma([if dark { 0 } else { 255 }])
+        })
+    }
+
+    #[test]
+    fn decodes_all_versions_and_levels() {
+        for version in 1..=25 {
+            for level in [EcLevel::L, EcLevel::M, EcLevel::Q, EcLevel::H] {
+                let Ok(code) = QrCode::with_version(PAYLOAD, Version::Normal(version), level) else {
+                    continue;
+                };
+                let image = render(&code, 3);
+                assert_eq!(decode(&image).as_deref(), Some(PAYLOAD), "版本 {} {:?}", version, level);
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_rotated_and_one_pixel_modules() {
+        let code = QrCode::with_error_correction_level(PAYLOAD, EcLevel::M).unwrap();
+        let image = render(&code, 1);
+        assert_eq!(decode(&image).as_deref(), Some(PAYLOAD));
+        for rotated in [imageops::rotate90(&image), imageops::rotate180(&image), imageops::rotate270(&image)] {
+            assert_eq!(decode(&rotated).as_deref(), Some(PAYLOAD));
+        }
+    }
+
+    /// 按仿射变换 `[a b; c d]`（以图片中心为原点）变换图片，最近邻采样，空白处为白色
+    fn warp(image: &GrayImage, [a, b, c, d]: [f64; 4]) -> GrayImage {
+        let (w, h) = (image.width() as f64, image.height() as f64);
+        let corners = [(-w / 2.0, -h / 2.0), (w / 2.0, -h / 2.0), (-w / 2.0, h / 2.0), (w / 2.0, h / 2.0)];
+        let mapped: Vec<(f64, f64)> = corners.iter().map(|&(x, y)| (a * x + b * y, c * x + d * y)).collect();
+        let out_w = mapped.iter().map(|p| p.0.abs()).fold(0.0, f64::max) * 2.0;
+        let out_h = mapped.iter().map(|p| p.1.abs()).fold(0.0, f64::max) * 2.0;
+        let det = a * d - b * c;
+        GrayImage::from_fn(out_w.ceil() as u32, out_h.ceil() as u32, |x, y| {
+            let (u, v) = (x as f64 + 0.5 - out_w / 2.0, y as f64 + 0.5 - out_h / 2.0);
+            let sx = (d * u - b * v) / det + w / 2.0;
+            let sy = (-c * u + a * v) / det + h / 2.0;
+            if sx < 0.0 || sy < 0.0 || sx >= w || sy >= h {
+                Luma([255])
+            } else {
+                *image.get_pixel(sx as u32, sy as u32)
+            }
+        })
+    }
+
+    fn rotation(degrees: f64) -> [f64; 4] {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        [cos, -sin, sin, cos]
+    }
+
+    #[test]
+    fn decodes_arbitrary_rotation() {
+        let code = QrCode::with_error_correction_level(PAYLOAD, EcLevel::M).unwrap();
+        let image = render(&code, 6);
+        for degrees in [7.0, 15.0, 30.0, 45.0, 60.0, 100.0, 135.0, 200.0, 315.0] {
+            assert_eq!(decode(&warp(&image, rotation(degrees))).as_deref(), Some(PAYLOAD), "旋转 {}°", degrees);
+        }
+    }
+
+    #[test]
+    fn decodes_skewed() {
+        let code = QrCode::with_error_correction_level(PAYLOAD, EcLevel::M).unwrap();
+        let image = render(&code, 6);
+        // 水平、竖直方向的错切，横向压缩（斜着拍摄），以及错切后再旋转
+        let skews = [
+            [1.0, 0.2, 0.0, 1.0],
+            [1.0, 0.0, -0.25, 1.0],
+            [0.7, 0.0, 0.0, 1.0],
+            [1.0, 0.15, 0.1, 0.9],
+        ];
+        for matrix in skews {
+            assert_eq!(decode(&warp(&image, matrix)).as_deref(), Some(PAYLOAD), "{:?}", matrix);
+        }
+        let [a, b, c, d] = rotation(25.0);
+        let sheared = [a + 0.2 * c, b + 0.2 * d, c, d];
+        assert_eq!(decode(&warp(&image, sheared)).as_deref(), Some(PAYLOAD));
+    }
+
+    #[test]
+    fn decodes_utf8_bytes() {
+        let text = "电子发票（普通发票）";
+        let code = QrCode::new(text).unwrap();
+        assert_eq!(decode(&render(&code, 4)).as_deref(), Some(text));
+    }
+
+    #[test]
+    fn rejects_images_without_code() {
+        assert_eq!(decode(&GrayImage::from_pixel(120, 120, Luma([255]))), None);
+        assert_eq!(decode(&GrayImage::from_pixel(2, 20000, Luma([0]))), None);
+    }
+}
@@ -0,0 +1,340 @@
+//! 发票二维码
+//!
+//! 增值税发票和全电发票的票面左上角印有二维码，内容为逗号分隔的版本、发票种类、
+//! 发票代码、发票号码、金额、开票日期、校验码和校验值，例如
+//! `01,32,,24442000000012345678,1130.00,20240115,,A1B2,`。
+//! 二维码由开票系统生成，不受文本层排版和字体编码影响，识别出时以二维码为准。
+//!
+//! 以二维码为准之前，先与文本层识别出的发票号码、开票日期和金额核对。
+//! 正常开具的发票两者总是一致，修改过票面文字的PDF（如改了金额）则会不一致。
+
+mod decoder;
+
+use image::GrayImage;
+use lopdf::Document;
+use serde::{Deserialize, Serialize};
+
+use crate::amount_words::parse_cents;
+use crate::extractor::InvoiceInfo;
+use crate::invoice_type::InvoiceType;
+use crate::pdf_images;
+
+/// 发票二维码中的信息
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InvoiceQr {
+    /// 发票种类代码，如 "32"（电子发票（普通发票））、"10"（增值税电子普通发票）
+    pub kind: String,
+    /// 发票代码，全电发票为空
+    pub invoice_code: String,
+    pub invoice_number: String,
+    /// 全电发票为价税合计，其他发票为不含税金额
+    pub amount: String,
+    /// 开票日期，格式为 YYYY-MM-DD
+    pub invoice_date: String,
+    /// 校验码，全电发票和专用发票为空
+    pub check_code: String,
+    /// 二维码原文
+    pub payload: String,
+}
+
+/// 二维码与文本识别结果不一致的字段
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QrMismatch {
+    /// 字段名称："发票号码"、"开票日期"、"价税合计" 或 "不含税金额"
+    pub field: String,
+    /// 从文本识别出的值
+    pub text: String,
+    /// 二维码中的值
+    pub qr: String,
+}
+
+impl std::fmt::Display for QrMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} ≠ 二维码 {}", self.field, self.text, self.qr)
+    }
+}
+
+impl InvoiceQr {
+    /// 解析二维码内容，不是发票二维码时返回 `None`
+    pub fn parse(payload: &str) -> Option<Self> {
+        let payload = payload.trim();
+        let fields: Vec<&str> = payload.split(',').map(str::trim).collect();
+        if fields.len() < 6 || fields[0] != "01" {
+            return None;
+        }
+
+        let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+        let (kind, code, number, amount, date) = (fields[1], fields[2], fields[3], fields[4], fields[5]);
+        if kind.len() != 2 || !digits(kind) || !digits(number) || (!code.is_empty() && !digits(code)) {
+            return None;
+        }
+        if date.len() != 8 || !digits(date) {
+            return None;
+        }
+        let (month, day) = (&date[4..6], &date[6..8]);
+        if !("01".."13").contains(&month) || !("01".."32").contains(&day) {
+            return None;
+        }
+
+        Some(Self {
+            kind: kind.to_string(),
+            invoice_code: code.to_string(),
+            invoice_number: number.to_string(),
+            amount: format_cents(parse_cents(amount)?),
+            invoice_date: format!("{}-{}-{}", &date[..4], month, day),
+            check_code: fields.get(6).map(|s| s.to_string()).unwrap_or_default(),
+            payload: payload.to_string(),
+        })
+    }
+
+    /// 发票种类代码对应的发票类型
+    pub fn invoice_type(&self) -> Option<InvoiceType> {
+        match self.kind.as_str() {
+            "01" => Some(InvoiceType::VatSpecial),
+            "04" => Some(InvoiceType::VatNormal),
+            "08" => Some(InvoiceType::VatElectronicSpecial),
+            "10" | "14" => Some(InvoiceType::VatElectronicNormal),
+            "11" => Some(InvoiceType::VatRoll),
+            "31" => Some(InvoiceType::ElectronicSpecial),
+            "32" => Some(InvoiceType::ElectronicNormal),
+            _ => None,
+        }
+    }
+
+    /// 是否为全电发票，其二维码中的金额是价税合计
+    pub fn is_fully_digital(&self) -> bool {
+        matches!(self.kind.as_str(), "31" | "32")
+    }
+
+    /// 与文本识别结果核对发票号码、开票日期和金额，文本中没有识别出的字段不参与核对，
+    /// 没有标签、猜测出的发票号码（[`InvoiceInfo::invoice_number_guessed`]）也不参与
+    ///
+    /// 返回不一致的字段，以及是否核对了至少一个字段。
+    pub fn compare(&self, info: &InvoiceInfo) -> (Vec<QrMismatch>, bool) {
+        let (amount_field, text_amount) = if self.is_fully_digital() {
+            ("价税合计", &info.amount)
+        } else {
+            ("不含税金额", &info.amount_without_tax)
+        };
+        let mut mismatches = Vec::new();
+        let mut compared = false;
+        let mut check = |field: &str, text: &str, qr: &str, same: bool| {
+            if text.is_empty() {
+                return;
+            }
+            compared = true;
+            if !same {
+                mismatches.push(QrMismatch {
+                    field: field.to_string(),
+                    text: text.to_string(),
+                    qr: qr.to_string(),
+                });
+            }
+        };
+        if !info.invoice_number_guessed {
+            check("发票号码", &info.invoice_number, &self.invoice_number, info.invoice_number == self.invoice_number);
+        }
+        check("开票日期", &info.invoice_date, &self.invoice_date, info.invoice_date == self.invoice_date);
+        check(amount_field, text_amount, &self.amount, parse_cents(text_amount) == parse_cents(&self.amount));
+        (mismatches, compared)
+    }
+
+    /// 与文本识别结果核对后，用二维码中的字段覆盖文本识别结果，并重新核对金额
+    pub(crate) fn apply(&self, info: &mut InvoiceInfo) {
+        let (mismatches, compared) = self.compare(info);
+        info.qr_verified = compared && mismatches.is_empty();
+        info.qr_mismatch = mismatches;
+
+        if let Some(invoice_type) = self.invoice_type() {
+            info.invoice_type = invoice_type;
+        }
+        info.invoice_number = self.invoice_number.clone();
+        info.invoice_number_guessed = false;
+        if !self.invoice_code.is_empty() {
+            info.invoice_code = self.invoice_code.clone();
+        }
+        if !self.check_code.is_empty() {
+            info.check_code = self.check_code.clone();
+        }
+        info.invoice_date = self.invoice_date.clone();
+        if self.is_fully_digital() {
+            info.amount = self.amount.clone();
+        } else {
+            info.amount_without_tax = self.amount.clone();
+        }
+        info.update_checks();
+        info.qr = Some(self.clone());
+    }
+}
+
+/// 以分为单位的金额格式化为两位小数
+fn format_cents(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{}{}.{:02}", sign, cents.abs() / 100, cents.abs() % 100)
+}
+
+/// 识别图片中的发票二维码
+pub fn read_qr_image(image: &GrayImage) -> Option<InvoiceQr> {
+    decoder::decode(image).and_then(|payload| InvoiceQr::parse(&payload))
+}
+
+/// 在PDF嵌入的图片中查找发票二维码，按页面顺序返回第一个
+pub(crate) fn read_qr_pdf(doc: &Document) -> Option<InvoiceQr> {
+    pdf_images::image_ids(doc)
+        .into_iter()
+        .filter_map(|id| pdf_images::to_gray(doc, id))
+        .find_map(|image| read_qr_image(&image))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fully_digital_invoice() {
+        let qr = InvoiceQr::parse("01,32,,24442000000012345678,1130,20240115,,A1B2,").unwrap();
+        assert_eq!(qr.kind, "32");
+        assert_eq!(qr.invoice_code, "");
+        assert_eq!(qr.invoice_number, "24442000000012345678");
+        assert_eq!(qr.amount, "1130.00");
+        assert_eq!(qr.invoice_date, "2024-01-15");
+        assert_eq!(qr.invoice_type(), Some(InvoiceType::ElectronicNormal));
+        assert!(qr.is_fully_digital());
+    }
+
+    #[test]
+    fn parses_vat_invoice_with_check_code() {
+        let qr = InvoiceQr::parse(" 01,10,044031900111,12345678,100.5,20200101,12345678901234567890,ABCD,\n").unwrap();
+        assert_eq!(qr.invoice_code, "044031900111");
+        assert_eq!(qr.amount, "100.50");
+        assert_eq!(qr.check_code, "12345678901234567890");
+        assert_eq!(qr.invoice_type(), Some(InvoiceType::VatElectronicNormal));
+        assert!(!qr.is_fully_digital());
+    }
+
+    #[test]
+    fn rejects_invalid_payloads() {
+        let invalid = [
+            // 月份、日期超出范围
+            "01,32,,24442000000012345678,1130.00,20241315,,A1B2,",
+            "01,32,,24442000000012345678,1130.00,20240100,,A1B2,",
+            "01,32,,24442000000012345678,1130.00,20240132,,A1B2,",
+            // 缺少开票日期
+            "01,32,,24442000000012345678,1130.00",
+            // 版本不是01
+            "02,32,,24442000000012345678,1130.00,20240115,,A1B2,",
+            // 种类、号码、金额不是数字
+            "01,3X,,24442000000012345678,1130.00,20240115,,A1B2,",
+            "01,32,,2444200000001234567A,1130.00,20240115,,A1B2,",
+            "01,32,,24442000000012345678,abc,20240115,,A1B2,",
+            "01,32,,24442000000012345678,1e3,20240115,,A1B2,",
+            "01,32,,24442000000012345678,inf,20240115,,A1B2,",
+            "https://example.com/",
+            "",
+        ];
+        for payload in invalid {
+            assert_eq!(InvoiceQr::parse(payload), None, "{}", payload);
+        }
+    }
+
+    const FULLY_DIGITAL: &str = "01,32,,24442000000012345678,1130.00,20240115,,A1B2,";
+    const VAT: &str = "01,10,044031900111,12345678,100.50,20200101,12345678901234567890,ABCD,";
+
+    fn fully_digital_info() -> InvoiceInfo {
+        InvoiceInfo {
+            invoice_number: "24442000000012345678".to_string(),
+            invoice_date: "2024-01-15".to_string(),
+            amount: "1130".to_string(),
+            amount_without_tax: "1000.00".to_string(),
+            tax_amount: "130.00".to_string(),
+            ..InvoiceInfo::default()
+        }
+    }
+
+    #[test]
+    fn all_fields_match() {
+        let qr = InvoiceQr::parse(FULLY_DIGITAL).unwrap();
+        let mut info = fully_digital_info();
+        assert_eq!(qr.compare(&info), (Vec::new(), true));
+
+        qr.apply(&mut info);
+        assert!(info.qr_verified);
+        assert!(info.qr_mismatch.is_empty());
+        assert_eq!(info.amount, "1130.00");
+        assert_eq!(info.invoice_type, InvoiceType::ElectronicNormal);
+        assert_eq!(info.qr.as_ref(), Some(&qr));
+    }
+
+    #[test]
+    fn edited_amount_is_reported_and_replaced() {
+        let qr = InvoiceQr::parse(FULLY_DIGITAL).unwrap();
+        let mut info = InvoiceInfo {
+            amount: "1,310.00".to_string(),
+            ..fully_digital_info()
+        };
+        let mismatch = QrMismatch {
+            field: "价税合计".to_string(),
+            text: "1,310.00".to_string(),
+            qr: "1130.00".to_string(),
+        };
+        assert_eq!(qr.compare(&info), (vec![mismatch.clone()], true));
+
+        info.update_checks();
+        assert!(info.amount_mismatch);
+        qr.apply(&mut info);
+        assert!(!info.qr_verified);
+        assert_eq!(info.qr_mismatch, [mismatch]);
+        // 以二维码为准，并重新核对金额
+        assert_eq!(info.amount, "1130.00");
+        assert!(!info.amount_mismatch);
+    }
+
+    #[test]
+    fn vat_invoice_compares_amount_without_tax() {
+        let qr = InvoiceQr::parse(VAT).unwrap();
+        let mut info = InvoiceInfo {
+            invoice_code: "044031900111".to_string(),
+            invoice_number: "12345678".to_string(),
+            invoice_date: "2020-01-01".to_string(),
+            amount: "106.53".to_string(),
+            amount_without_tax: "100.50".to_string(),
+            tax_amount: "6.03".to_string(),
+            ..InvoiceInfo::default()
+        };
+        // 价税合计与二维码中的不含税金额不同，但不参与核对
+        assert_eq!(qr.compare(&info), (Vec::new(), true));
+
+        info.amount_without_tax = "100.00".to_string();
+        let (mismatches, compared) = qr.compare(&info);
+        assert!(compared);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].field, "不含税金额");
+
+        qr.apply(&mut info);
+        assert_eq!(info.amount_without_tax, "100.50");
+        assert_eq!(info.amount, "106.53");
+        assert_eq!(info.check_code, "12345678901234567890");
+    }
+
+    #[test]
+    fn nothing_to_compare() {
+        // 文本中没有识别出任何字段（如文字识别失败的照片），字段全部取自二维码，但不算核对一致
+        let qr = InvoiceQr::parse(FULLY_DIGITAL).unwrap();
+        let mut info = InvoiceInfo::default();
+        assert_eq!(qr.compare(&info), (Vec::new(), false));
+
+        qr.apply(&mut info);
+        assert!(!info.qr_verified);
+        assert!(info.qr_mismatch.is_empty());
+        assert_eq!(info.invoice_number, "24442000000012345678");
+        assert_eq!(info.invoice_date, "2024-01-15");
+        assert_eq!(info.amount, "1130.00");
+    }
+
+    #[test]
+    fn formats_cents() {
+        assert_eq!(format_cents(113000), "1130.00");
+        assert_eq!(format_cents(5), "0.05");
+        assert_eq!(format_cents(-12345), "-123.45");
+    }
+}
@@ -0,0 +1,148 @@
+//! 字段识别规则
+//!
+//! 所有正则表达式在 [`RuleSet::new`] 中编译一次，批量识别时各线程共用
+//! [`RuleSet::shared`] 返回的同一份规则。关键词、排除的文本行和金额上限
+//! 可以由配置文件修改，见 [`RuleSet::from_config`]。
+
+use regex::{Regex, RegexSet};
+use std::sync::OnceLock;
+
+use crate::config::RuleConfig;
+use crate::error::{InvoiceError, Result};
+
+/// 排除的文本行：表头、监制章、金额行等不会是公司名称
+pub(crate) const EXCLUDE_PATTERNS: [&str; 4] = [
+    r"\*[^*]+\*",
+    r"项目|规格|单位|数量|单价|金额|税率|税额|合计|备注|开票人|下载次数|发票号码|开票日期",
+    r"国家税务总局|发票监制|电子发票|普通发票|广东省税务局",
+    r"价税合计|大写|小写",
+];
+
+/// 销售方关键词
+pub(crate) const SELLER_KEYWORDS: [&str; 18] = [
+    "有限公司", "股份有限公司", "科技", "网络", "文化", "婴童",
+    "贸易", "酒店", "饭店", "娱乐", "百货", "商店",
+    "餐饮店", "饮食店", "加油站", "石油化工",
+    "商行", "电子商务商行",
+];
+
+/// 在税号附近查找销售方名称时使用的关键词
+pub(crate) const CONTEXT_KEYWORDS: [&str; 9] = ["店", "商行", "有限公司", "商贸", "科技", "贸易", "酒店", "饭店", "餐饮"];
+
+/// 金额上限，超过的¥数值视为误识别
+pub(crate) const MAX_AMOUNT: f64 = 10_000_000.0;
+
+/// 预编译的识别规则
+#[derive(Debug, Clone)]
+pub struct RuleSet {
+    pub(crate) invoice_number: Regex,
+    pub(crate) invoice_code_label: Regex,
+    pub(crate) invoice_code: Regex,
+    pub(crate) old_invoice_number_label: Regex,
+    pub(crate) old_invoice_number: Regex,
+    pub(crate) check_code: Regex,
+    pub(crate) date: Regex,
+    pub(crate) tax_number: Regex,
+    pub(crate) buyer_block: Regex,
+    pub(crate) seller_block: Regex,
+    pub(crate) party_name: Regex,
+    pub(crate) party_tax_number: Regex,
+    pub(crate) item: Regex,
+    pub(crate) item_table_header: Regex,
+    pub(crate) item_table_end: Regex,
+    pub(crate) item_number: Regex,
+    pub(crate) item_tax_rate: Regex,
+    pub(crate) exclude: RegexSet,
+    pub(crate) seller_keywords: Vec<String>,
+    pub(crate) context_keywords: Vec<Regex>,
+    pub(crate) subtotal: Regex,
+    pub(crate) total_lowercase: Regex,
+    pub(crate) tax_rate: Regex,
+    pub(crate) amount_in_words: Regex,
+    pub(crate) amount_after_yuanzheng: Regex,
+    pub(crate) amount: Regex,
+    pub(crate) max_amount: f64,
+    pub(crate) pdf_filename_amount: Regex,
+    pub(crate) image_filename_amount: Regex,
+}
+
+impl RuleSet {
+    /// 编译内置规则
+    pub fn new() -> Self {
+        Self::from_config(&RuleConfig::default()).expect("内置规则有效")
+    }
+
+    /// 按配置编译规则，排除的文本行不是有效的正则表达式时返回错误
+    pub fn from_config(config: &RuleConfig) -> Result<Self> {
+        let exclude = RegexSet::new(&config.exclude_lines).map_err(|e| {
+            // RegexSet 的错误不指明是哪一条，逐条编译找出无效的规则
+            let pattern = config
+                .exclude_lines
+                .iter()
+                .find(|p| Regex::new(p).is_err())
+                .cloned()
+                .unwrap_or_default();
+            InvoiceError::InvalidRule {
+                pattern,
+                message: e.to_string(),
+            }
+        })?;
+
+        Ok(Self {
+            invoice_number: Regex::new(r"\b(\d{20})\b").unwrap(),
+            invoice_code_label: Regex::new(r"发票代码\s*[:：]?\s*(\d{12})\b").unwrap(),
+            invoice_code: Regex::new(r"\b(\d{12})\b").unwrap(),
+            old_invoice_number_label: Regex::new(r"发票号码\s*[:：]?\s*(\d{8})\b").unwrap(),
+            old_invoice_number: Regex::new(r"\b(\d{8})\b").unwrap(),
+            check_code: Regex::new(r"(\d{5})\s+(\d{5})\s+(\d{5})\s+(\d{5})").unwrap(),
+            date: Regex::new(r"(\d{4})年(\d{1,2})月(\d{1,2})日").unwrap(),
+            tax_number: Regex::new(r"\b(?:[0-9A-Z]{15}|[0-9A-Z]{17,18}|[0-9A-Z]{20})\b").unwrap(),
+            buyer_block: Regex::new(r"购\s*买\s*方").unwrap(),
+            seller_block: Regex::new(r"销\s*售\s*方").unwrap(),
+            party_name: Regex::new(r"名\s*称\s*[:：][ \t]*([^\s:：]+)?").unwrap(),
+            party_tax_number: Regex::new(
+                r"(?:纳\s*税\s*人\s*识\s*别\s*号|统\s*一\s*社\s*会\s*信\s*用\s*代\s*码)[^:：\n]{0,16}[:：][ \t]*([0-9A-Z]{15,20}\b)?",
+            )
+            .unwrap(),
+            item: Regex::new(r"\*([^*]+)\*").unwrap(),
+            item_table_header: Regex::new(r"规格型号").unwrap(),
+            item_table_end: Regex::new(r"^合\s*计").unwrap(),
+            item_number: Regex::new(r"^-?[\d,]*\.?\d+$").unwrap(),
+            item_tax_rate: Regex::new(r"^(?:\d{1,2}(?:\.\d+)?%|免税|不征税|\*+)$").unwrap(),
+            exclude,
+            seller_keywords: config.seller_keywords.clone(),
+            context_keywords: config
+                .context_keywords
+                .iter()
+                .map(|kw| Regex::new(&format!(r"([^\s\n]+{}[^\s\n]*)", regex::escape(kw))).unwrap())
+                .collect(),
+            subtotal: Regex::new(
+                r"合\s*计\s*[¥￥]\s*(-?[\d,]+\.?\d*)(?:\s*(?:[¥￥]\s*(-?[\d,]+\.?\d*)|(\*{3})))?",
+            )
+            .unwrap(),
+            total_lowercase: Regex::new(r"小\s*写\s*[)）]?\s*[¥￥]\s*(-?[\d,]+\.?\d*)").unwrap(),
+            tax_rate: Regex::new(r"(\d{1,2}(?:\.\d+)?%|免税|不征税)").unwrap(),
+            amount_in_words: Regex::new(
+                r"负?[零〇壹贰貳叁參肆伍陆陸柒捌玖拾佰仟万萬亿億]+[圆元圓](?:零|[零〇壹贰貳叁參肆伍陆陸柒捌玖][角分])*[整正]?",
+            )
+            .unwrap(),
+            amount_after_yuanzheng: Regex::new(r"圆整\s*[¥￥]?\s*([\d,]+\.?\d*)").unwrap(),
+            amount: Regex::new(r"[¥￥]\s*([\d,]+\.?\d*)").unwrap(),
+            max_amount: config.max_amount,
+            pdf_filename_amount: Regex::new(r"(\d+\.?\d*)\.pdf").unwrap(),
+            image_filename_amount: Regex::new(r"(\d+\.?\d*)\.(?:PNG|JPG|JPEG)").unwrap(),
+        })
+    }
+
+    /// 进程内共享的内置规则，首次调用时编译
+    pub fn shared() -> &'static RuleSet {
+        static RULES: OnceLock<RuleSet> = OnceLock::new();
+        RULES.get_or_init(RuleSet::new)
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,145 @@
+//! 纳税人识别号校验
+//!
+//! 目前的纳税人识别号是18位统一社会信用代码（GB 32100），最后一位是校验码。
+//! 旧版识别号还有：15位（6位行政区划码 + 9位组织机构代码）、
+//! 18位或20位（个人身份证号，20位为身份证号后加2位顺序码）和17位。
+//! 17位识别号没有校验位，结果为 [`TaxIdCheck::Unverifiable`]，既不算有效也不算无效。
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// 统一社会信用代码字符集，不使用 I、O、S、V、Z
+const USCC_CHARSET: &str = "0123456789ABCDEFGHJKLMNPQRTUWXY";
+/// 统一社会信用代码前17位的加权因子
+const USCC_WEIGHTS: [u32; 17] = [1, 3, 9, 27, 19, 26, 16, 17, 20, 29, 25, 13, 8, 24, 10, 30, 28];
+/// 组织机构代码（GB 11714）前8位的加权因子
+const ORG_CODE_WEIGHTS: [u32; 8] = [3, 7, 9, 10, 5, 8, 4, 2];
+/// 身份证号（GB 11643）前17位的加权因子
+const ID_CARD_WEIGHTS: [u32; 17] = [7, 9, 10, 5, 8, 4, 2, 1, 6, 3, 7, 9, 10, 5, 8, 4, 2];
+
+/// 纳税人识别号的校验结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaxIdCheck {
+    /// 校验位正确
+    Valid,
+    /// 17位数字的旧版识别号，没有校验位，无法判断真伪
+    Unverifiable,
+    /// 长度不对、含有非法字符或校验位错误
+    Invalid,
+}
+
+impl TaxIdCheck {
+    /// 中文名称，用于表格和命令行输出
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Valid => "有效",
+            Self::Unverifiable => "无法校验",
+            Self::Invalid => "无效",
+        }
+    }
+}
+
+impl fmt::Display for TaxIdCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.label())
+    }
+}
+
+/// 校验纳税人识别号：15、18、20位的按校验位判断，17位的无法校验，其他长度无效
+pub fn check_tax_id(id: &str) -> TaxIdCheck {
+    if !id.is_ascii() {
+        return TaxIdCheck::Invalid;
+    }
+    let valid = match id.len() {
+        18 => is_valid_uscc(id) || is_valid_id_card(id),
+        15 => id[..6].bytes().all(|b| b.is_ascii_digit()) && is_valid_org_code(&id[6..]),
+        20 => is_valid_id_card(&id[..18]) && id[18..].bytes().all(|b| b.is_ascii_digit()),
+        17 if id.bytes().all(|b| b.is_ascii_digit()) => {
+            return TaxIdCheck::Unverifiable;
+        }
+        _ => false,
+    };
+    if valid {
+        TaxIdCheck::Valid
+    } else {
+        TaxIdCheck::Invalid
+    }
+}
+
+/// 18位统一社会信用代码的校验码是否正确
+pub fn is_valid_uscc(code: &str) -> bool {
+    let values: Option<Vec<u32>> = code.chars().map(|c| USCC_CHARSET.find(c).map(|i| i as u32)).collect();
+    let Some(values) = values.filter(|v| v.len() == 18) else {
+        return false;
+    };
+
+    let sum: u32 = values[..17].iter().zip(USCC_WEIGHTS).map(|(v, w)| v * w).sum();
+    (31 - sum % 31) % 31 == values[17]
+}
+
+/// 9位组织机构代码的校验码是否正确
+fn is_valid_org_code(code: &str) -> bool {
+    let bytes = code.as_bytes();
+    if bytes.len() != 9 || !bytes[..8].iter().all(|b| b.is_ascii_digit() || b.is_ascii_uppercase()) {
+        return false;
+    }
+
+    let value = |b: u8| if b.is_ascii_digit() { (b - b'0') as u32 } else { (b - b'A') as u32 + 10 };
+    let sum: u32 = bytes[..8].iter().zip(ORG_CODE_WEIGHTS).map(|(b, w)| value(*b) * w).sum();
+    let expected = match 11 - sum % 11 {
+        10 => b'X',
+        11 => b'0',
+        n => b'0' + n as u8,
+    };
+    bytes[8] == expected
+}
+
+/// 18位身份证号的校验码是否正确
+fn is_valid_id_card(id: &str) -> bool {
+    let bytes = id.as_bytes();
+    if bytes.len() != 18 || !bytes[..17].iter().all(u8::is_ascii_digit) {
+        return false;
+    }
+
+    let sum: u32 = bytes[..17].iter().zip(ID_CARD_WEIGHTS).map(|(b, w)| (b - b'0') as u32 * w).sum();
+    bytes[17] == b"10X98765432"[(sum % 11) as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uscc_check_digit() {
+        for id in ["91350100M000100Y43", "91440300MA5G0L2Q3P", "91310115MA1H7XKQ5R"] {
+            assert!(is_valid_uscc(id), "{}", id);
+            assert_eq!(check_tax_id(id), TaxIdCheck::Valid, "{}", id);
+        }
+        // 校验码错误、含有不使用的字母、长度不对
+        for id in ["91350100M000100Y44", "91350100M000100I43", "91350100M000100Y4", "91350100m000100y43"] {
+            assert!(!is_valid_uscc(id), "{}", id);
+            assert_eq!(check_tax_id(id), TaxIdCheck::Invalid, "{}", id);
+        }
+    }
+
+    #[test]
+    fn old_tax_ids() {
+        // 6位行政区划码 + 组织机构代码
+        assert_eq!(check_tax_id("110105D2143569X"), TaxIdCheck::Valid);
+        assert_eq!(check_tax_id("440300MA5G0L2Q3"), TaxIdCheck::Valid);
+        assert_eq!(check_tax_id("110105D21435690"), TaxIdCheck::Invalid);
+        assert_eq!(check_tax_id("11010AD2143569X"), TaxIdCheck::Invalid);
+        // 身份证号，校验码可以是X，20位的后加2位顺序码
+        assert_eq!(check_tax_id("11010519491231002X"), TaxIdCheck::Valid);
+        assert_eq!(check_tax_id("440524188001010014"), TaxIdCheck::Valid);
+        assert_eq!(check_tax_id("11010519491231002X01"), TaxIdCheck::Valid);
+        assert_eq!(check_tax_id("110105194912310021"), TaxIdCheck::Invalid);
+        assert_eq!(check_tax_id("11010519491231002X0A"), TaxIdCheck::Invalid);
+        // 17位没有校验位，无法判断真伪
+        assert_eq!(check_tax_id("12345678901234567"), TaxIdCheck::Unverifiable);
+        assert_eq!(check_tax_id("1234567890123456A"), TaxIdCheck::Invalid);
+        assert_eq!(check_tax_id("9135010012345678"), TaxIdCheck::Invalid);
+        assert_eq!(check_tax_id("９１３５０１００Ｍ０００１００Ｙ４３"), TaxIdCheck::Invalid);
+    }
+}